@@ -0,0 +1,74 @@
+use super::array::{new_array, read_f32};
+use super::brushes::{apply_brush, compute_very_big_square_brush, Brush};
+use super::debug::{now, since};
+use super::generator::{generate_feasible_design, GeneratorOptions};
+use super::layers::Layer;
+use super::metrics::{latent_agreement, LatentDisagreement};
+use super::multiscale::generate_feasible_design_coarse_to_fine;
+use super::stencil::Stencil;
+
+/// Benchmark brush stamping and (direct and coarse-to-fine) generation on the
+/// 300x300 fixture.
+pub fn test_bench() {
+    let seed = 42;
+    let (m, n) = (300, 300);
+    let latent_t = read_f32(&format!("latent_t_{seed}_{m}x{n}.bin"));
+    for (size, notch) in [(5, 1), (10, 2), (15, 3)] {
+        let brush = Brush::notched_square(size, notch);
+        println!("brush {size}x{size} (notch {notch}):");
+        bench_stamping((m, n), &brush);
+        bench_stamping((m, n), &compute_very_big_square_brush(&brush));
+
+        let start_time = now();
+        let design = generate_feasible_design((m, n), &latent_t, brush.clone(), false).unwrap();
+        let direct_time = since(start_time);
+        println!(
+            "  generate_feasible_design: {direct_time:.3}s (latent agreement: {:.4}, disagreement score: {:.4})",
+            latent_agreement(&design[Layer::Solid], &latent_t),
+            LatentDisagreement::of(&design, &latent_t).score
+        );
+
+        for factor in [3, 4] {
+            let start_time = now();
+            let (design, _) = generate_feasible_design_coarse_to_fine(
+                (m, n),
+                &latent_t,
+                &brush,
+                factor,
+                &GeneratorOptions::default(),
+            )
+            .unwrap();
+            let time = since(start_time);
+            println!(
+                "  generate_feasible_design_coarse_to_fine (factor {factor}): {time:.3}s ({:.1}x, latent agreement: {:.4}, disagreement score: {:.4}, feasible: {})",
+                direct_time / time,
+                latent_agreement(&design[Layer::Solid], &latent_t),
+                LatentDisagreement::of(&design, &latent_t).score,
+                design.is_feasible()
+            );
+        }
+    }
+}
+
+/// Stamp a brush at every position of the grid, once with `apply_brush` and
+/// once with a precomputed `Stencil`.
+fn bench_stamping(shape: (usize, usize), brush: &Brush) {
+    let (m, n) = shape;
+    let mut array = new_array(m * n, false);
+
+    let start_time = now();
+    for k in 0..m * n {
+        apply_brush(shape, &mut array, brush, (k / n, k % n), k % 2 == 0);
+    }
+    let brush_time = since(start_time);
+
+    let start_time = now();
+    let stencil = Stencil::new(brush, shape);
+    for k in 0..m * n {
+        stencil.apply(&mut array, (k / n, k % n), k % 2 == 0);
+    }
+    let stencil_time = since(start_time);
+
+    let (size, _) = brush.shape;
+    println!("  stamp {size}x{size}: apply_brush {brush_time:.3}s, stencil {stencil_time:.3}s");
+}
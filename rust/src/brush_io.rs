@@ -0,0 +1,509 @@
+use super::brushes::{Brush, BrushError};
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufWriter, Read, Write};
+use std::path::Path;
+
+pub fn test_brush_io() {
+    let brush = Brush::from_ascii(
+        "
+        .###.
+        #####
+        #####
+        #####
+        .###.
+        ",
+    )
+    .unwrap();
+    brush.visualize();
+    print!("{}", brush.to_ascii());
+
+    let dir = std::env::temp_dir();
+    for extension in ["txt", "pbm", "pgm", "png"] {
+        let path = dir.join(format!("brush.{extension}"));
+        let path = path.to_str().unwrap();
+        write_brush(path, &brush).unwrap();
+        let read = read_brush(path, &Threshold::default()).unwrap();
+        println!("{path}: round trip ok: {}", read.mask() == brush.mask());
+    }
+
+    // an image without dark pixels is a valid mask, but not a brush
+    for extension in ["txt", "pgm", "png"] {
+        let path = dir.join(format!("empty.{extension}"));
+        let path = path.to_str().unwrap();
+        write_mask(path, (3, 3), &[false; 9]).unwrap();
+        match read_brush(path, &Threshold::default()) {
+            Ok(_) => println!("{path}: loaded an empty brush"),
+            Err(error) => println!("{error}"),
+        }
+    }
+}
+
+/// Which pixels of an image belong to the brush.
+///
+/// Images are converted to a brightness between 0 (black) and 1 (white).
+/// Brush pixels are dark: their brightness is below `level`, or above it when
+/// `invert` is set. Transparent pixels count as white.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Threshold {
+    pub level: f32,
+    pub invert: bool,
+}
+
+impl Default for Threshold {
+    fn default() -> Self {
+        Self {
+            level: 0.5,
+            invert: false,
+        }
+    }
+}
+
+impl Threshold {
+    pub fn apply(&self, brightness: f32) -> bool {
+        (brightness < self.level) != self.invert
+    }
+}
+
+/// Why the content of a brush file, or ASCII art, does not describe a brush.
+#[derive(Debug)]
+pub enum BrushParseError {
+    /// a line of ASCII art is longer or shorter than the first one.
+    RaggedLine {
+        line: usize,
+        pixels: usize,
+        expected: usize,
+    },
+    /// ASCII art contains a character other than `#` and `.`.
+    UnexpectedCharacter {
+        line: usize,
+        character: char,
+    },
+    Utf8(std::string::FromUtf8Error),
+    MissingHeader,
+    TruncatedHeader,
+    UnsupportedImageType(String),
+    InvalidHeaderValue(String),
+    InvalidMaxValue(usize),
+    InvalidPixelValue(String),
+    PixelCount {
+        expected: usize,
+        found: usize,
+    },
+    Png(png::DecodingError),
+    /// the mask is not a brush the generator can use (e.g. it is empty).
+    Brush(BrushError),
+}
+
+impl fmt::Display for BrushParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::RaggedLine {
+                line,
+                pixels,
+                expected,
+            } => write!(f, "line {line} has {pixels} pixels, expected {expected}"),
+            Self::UnexpectedCharacter { line, character } => {
+                write!(f, "unexpected character '{character}' on line {line}")
+            }
+            Self::Utf8(error) => write!(f, "{error}"),
+            Self::MissingHeader => write!(f, "missing header"),
+            Self::TruncatedHeader => write!(f, "truncated header"),
+            Self::UnsupportedImageType(magic) => write!(f, "unsupported image type '{magic}'"),
+            Self::InvalidHeaderValue(token) => write!(f, "invalid header value '{token}'"),
+            Self::InvalidMaxValue(maxval) => write!(f, "invalid maximum value {maxval}"),
+            Self::InvalidPixelValue(token) => write!(f, "invalid pixel value '{token}'"),
+            Self::PixelCount { expected, found } => {
+                write!(f, "expected {expected} pixels, found {found}")
+            }
+            Self::Png(error) => write!(f, "{error}"),
+            Self::Brush(error) => {
+                let issues: Vec<String> = error.issues.iter().map(|i| i.to_string()).collect();
+                write!(f, "{}", issues.join(", "))
+            }
+        }
+    }
+}
+
+impl Error for BrushParseError {}
+
+impl From<BrushError> for BrushParseError {
+    fn from(error: BrushError) -> Self {
+        Self::Brush(error)
+    }
+}
+
+#[derive(Debug)]
+pub enum BrushFileError {
+    Io(std::io::Error),
+    /// the file content does not describe a brush (or mask).
+    Format {
+        path: String,
+        error: BrushParseError,
+    },
+    /// a PNG image could not be encoded.
+    Encoding {
+        path: String,
+        error: png::EncodingError,
+    },
+    /// the file extension is not one of `txt`, `pbm`, `pgm` or `png`.
+    UnknownExtension {
+        path: String,
+    },
+}
+
+impl fmt::Display for BrushFileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "{error}"),
+            Self::Format { path, error } => write!(f, "invalid brush file '{path}': {error}."),
+            Self::Encoding { path, error } => write!(f, "cannot write '{path}': {error}."),
+            Self::UnknownExtension { path } => write!(
+                f,
+                "unknown brush file type '{path}' (expected .txt, .pbm, .pgm or .png)."
+            ),
+        }
+    }
+}
+
+impl Error for BrushFileError {}
+
+impl From<std::io::Error> for BrushFileError {
+    fn from(error: std::io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+/// Read a brush from an ASCII-art (`.txt`), PBM, PGM or PNG file.
+///
+/// The brush origin is the center pixel (see `Brush::from_f32_mask`).
+/// `threshold` is ignored for ASCII art. Rejects brushes the generator cannot
+/// use (see `Brush::check`), e.g. an image without any dark pixel.
+pub fn read_brush(path: &str, threshold: &Threshold) -> Result<Brush, BrushFileError> {
+    let (shape, mask) = read_mask(path, threshold)?;
+    brush_from_mask(shape, &mask).map_err(|error| BrushFileError::Format {
+        path: path.to_string(),
+        error,
+    })
+}
+
+/// Write a brush as ASCII art (`.txt`), plain PBM, plain PGM or PNG.
+pub fn write_brush(path: &str, brush: &Brush) -> Result<(), BrushFileError> {
+    write_mask(path, brush.shape, &brush.mask())
+}
+
+/// Read a boolean mask in any of the brush file formats.
+pub fn read_mask(
+    path: &str,
+    threshold: &Threshold,
+) -> Result<((usize, usize), Vec<bool>), BrushFileError> {
+    let format_error = |error: BrushParseError| BrushFileError::Format {
+        path: path.to_string(),
+        error,
+    };
+    let extension = extension(path)?;
+    let mut bytes = Vec::new();
+    File::open(path)?.read_to_end(&mut bytes)?;
+    match extension.as_str() {
+        "txt" => {
+            let text =
+                String::from_utf8(bytes).map_err(|e| format_error(BrushParseError::Utf8(e)))?;
+            parse_ascii(&text).map_err(format_error)
+        }
+        "pbm" | "pgm" => {
+            let (shape, brightness) = parse_pnm(&bytes).map_err(format_error)?;
+            Ok((
+                shape,
+                brightness.iter().map(|b| threshold.apply(*b)).collect(),
+            ))
+        }
+        _ => {
+            let (shape, brightness) = parse_png(&bytes).map_err(format_error)?;
+            Ok((
+                shape,
+                brightness.iter().map(|b| threshold.apply(*b)).collect(),
+            ))
+        }
+    }
+}
+
+/// Write a boolean mask in any of the brush file formats (`true` is dark).
+pub fn write_mask(path: &str, shape: (usize, usize), mask: &[bool]) -> Result<(), BrushFileError> {
+    let (m, n) = shape;
+    let extension = extension(path)?;
+    let mut file = BufWriter::new(File::create(path)?);
+    let rows = (0..m).map(|i| &mask[i * n..(i + 1) * n]);
+    match extension.as_str() {
+        "txt" => {
+            for row in rows {
+                let line: String = row.iter().map(|b| if *b { '#' } else { '.' }).collect();
+                writeln!(file, "{line}")?;
+            }
+        }
+        "pbm" => {
+            writeln!(file, "P1\n{n} {m}")?;
+            for row in rows {
+                let line: Vec<&str> = row.iter().map(|b| if *b { "1" } else { "0" }).collect();
+                writeln!(file, "{}", line.join(" "))?;
+            }
+        }
+        "pgm" => {
+            writeln!(file, "P2\n{n} {m}\n255")?;
+            for row in rows {
+                let line: Vec<&str> = row.iter().map(|b| if *b { "0" } else { "255" }).collect();
+                writeln!(file, "{}", line.join(" "))?;
+            }
+        }
+        _ => {
+            let data: Vec<u8> = mask.iter().map(|b| if *b { 0 } else { 255 }).collect();
+            encode_png(&mut file, path, shape, png::ColorType::Grayscale, &data)?;
+        }
+    }
+    file.flush()?;
+    Ok(())
+}
+
+/// Write `data` (row-major, 8 bits per channel of `color`) as a PNG image of `shape`.
+pub fn write_png(
+    path: &str,
+    shape: (usize, usize),
+    color: png::ColorType,
+    data: &[u8],
+) -> Result<(), BrushFileError> {
+    let mut file = BufWriter::new(File::create(path)?);
+    encode_png(&mut file, path, shape, color, data)?;
+    file.flush()?;
+    Ok(())
+}
+
+fn encode_png<W: Write>(
+    writer: W,
+    path: &str,
+    shape: (usize, usize),
+    color: png::ColorType,
+    data: &[u8],
+) -> Result<(), BrushFileError> {
+    let (m, n) = shape;
+    let mut encoder = png::Encoder::new(writer, n as u32, m as u32);
+    encoder.set_color(color);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder
+        .write_header()
+        .and_then(|mut writer| writer.write_image_data(data))
+        .map_err(|error| BrushFileError::Encoding {
+            path: path.to_string(),
+            error,
+        })?;
+    Ok(())
+}
+
+impl Brush {
+    /// A brush from ASCII art: `#` for brush pixels and `.` for the rest.
+    ///
+    /// Leading and trailing whitespace of every line, and empty lines, are
+    /// ignored. Rejects ragged lines and brushes the generator cannot use
+    /// (see `Brush::check`), e.g. those without any `#`.
+    pub fn from_ascii(text: &str) -> Result<Self, BrushParseError> {
+        let (shape, mask) = parse_ascii(text)?;
+        brush_from_mask(shape, &mask)
+    }
+
+    pub fn to_ascii(&self) -> String {
+        let (_, n) = self.shape;
+        let mut text = String::new();
+        for (k, b) in self.mask().iter().enumerate() {
+            text.push(if *b { '#' } else { '.' });
+            if k % n == n - 1 {
+                text.push('\n');
+            }
+        }
+        text
+    }
+}
+
+fn brush_from_mask(shape: (usize, usize), mask: &[bool]) -> Result<Brush, BrushParseError> {
+    let mask = mask.iter().map(|b| *b as u8 as f32).collect();
+    let brush = Brush::from_f32_mask(shape, &mask);
+    brush.check()?;
+    Ok(brush)
+}
+
+fn extension(path: &str) -> Result<String, BrushFileError> {
+    let extension = Path::new(path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase());
+    match extension.as_deref() {
+        Some("txt") | Some("pbm") | Some("pgm") | Some("png") => Ok(extension.unwrap()),
+        _ => Err(BrushFileError::UnknownExtension {
+            path: path.to_string(),
+        }),
+    }
+}
+
+fn parse_ascii(text: &str) -> Result<((usize, usize), Vec<bool>), BrushParseError> {
+    let lines: Vec<&str> = text
+        .lines()
+        .map(|l| l.trim())
+        .filter(|l| !l.is_empty())
+        .collect();
+    let n = lines.first().map(|l| l.chars().count()).unwrap_or(0);
+    let mut mask = Vec::new();
+    for (i, line) in lines.iter().enumerate() {
+        if line.chars().count() != n {
+            return Err(BrushParseError::RaggedLine {
+                line: i + 1,
+                pixels: line.chars().count(),
+                expected: n,
+            });
+        }
+        for c in line.chars() {
+            match c {
+                '#' => mask.push(true),
+                '.' => mask.push(false),
+                _ => {
+                    return Err(BrushParseError::UnexpectedCharacter {
+                        line: i + 1,
+                        character: c,
+                    })
+                }
+            }
+        }
+    }
+    Ok(((lines.len(), n), mask))
+}
+
+/// Parse a (plain or raw) PBM or PGM image into its brightness.
+fn parse_pnm(bytes: &[u8]) -> Result<((usize, usize), Vec<f32>), BrushParseError> {
+    let mut pos = 0;
+    let magic = next_token(bytes, &mut pos).ok_or(BrushParseError::MissingHeader)?;
+    let bitmap = match magic.as_str() {
+        "P1" | "P4" => true,
+        "P2" | "P5" => false,
+        _ => return Err(BrushParseError::UnsupportedImageType(magic)),
+    };
+    let mut number = || -> Result<usize, BrushParseError> {
+        let token = next_token(bytes, &mut pos).ok_or(BrushParseError::TruncatedHeader)?;
+        match token.parse() {
+            Ok(value) => Ok(value),
+            Err(_) => Err(BrushParseError::InvalidHeaderValue(token)),
+        }
+    };
+    let n = number()?;
+    let m = number()?;
+    let maxval = if bitmap { 1 } else { number()? };
+    if (maxval == 0) | (maxval > 65535) {
+        return Err(BrushParseError::InvalidMaxValue(maxval));
+    }
+
+    let brightness: Vec<f32> = match magic.as_str() {
+        "P1" => {
+            // plain bitmaps may omit the whitespace between pixels
+            let bits: Vec<f32> = bytes[pos..]
+                .iter()
+                .filter(|b| !b.is_ascii_whitespace())
+                .take(m * n)
+                .map(|b| (*b == b'0') as u8 as f32)
+                .collect();
+            bits
+        }
+        "P2" => {
+            let mut values = Vec::with_capacity(m * n);
+            while values.len() < m * n {
+                let Some(token) = next_token(bytes, &mut pos) else {
+                    break;
+                };
+                let Ok(value) = token.parse::<f32>() else {
+                    return Err(BrushParseError::InvalidPixelValue(token));
+                };
+                values.push(value / maxval as f32);
+            }
+            values
+        }
+        _ => {
+            // a single whitespace byte separates the header from the raster
+            let raster = &bytes[(pos + 1).min(bytes.len())..];
+            if bitmap {
+                let row_bytes = n.div_ceil(8);
+                (0..m * n)
+                    .filter_map(|k| {
+                        let (i, j) = (k / n, k % n);
+                        let byte = raster.get(i * row_bytes + j / 8)?;
+                        Some(((byte >> (7 - j % 8)) & 1 == 0) as u8 as f32)
+                    })
+                    .collect()
+            } else if maxval < 256 {
+                raster
+                    .iter()
+                    .take(m * n)
+                    .map(|b| *b as f32 / maxval as f32)
+                    .collect()
+            } else {
+                raster
+                    .chunks_exact(2)
+                    .take(m * n)
+                    .map(|b| u16::from_be_bytes([b[0], b[1]]) as f32 / maxval as f32)
+                    .collect()
+            }
+        }
+    };
+    if brightness.len() != m * n {
+        return Err(BrushParseError::PixelCount {
+            expected: m * n,
+            found: brightness.len(),
+        });
+    }
+    Ok(((m, n), brightness))
+}
+
+/// The next whitespace-separated token of a PNM header, skipping `#` comments.
+fn next_token(bytes: &[u8], pos: &mut usize) -> Option<String> {
+    loop {
+        while (*pos < bytes.len()) && bytes[*pos].is_ascii_whitespace() {
+            *pos += 1;
+        }
+        if (*pos < bytes.len()) && (bytes[*pos] == b'#') {
+            while (*pos < bytes.len()) && (bytes[*pos] != b'\n') {
+                *pos += 1;
+            }
+            continue;
+        }
+        break;
+    }
+    let start = *pos;
+    while (*pos < bytes.len()) && !bytes[*pos].is_ascii_whitespace() {
+        *pos += 1;
+    }
+    if start == *pos {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&bytes[start..*pos]).to_string())
+}
+
+/// Decode a PNG image into its brightness (composited over white).
+fn parse_png(bytes: &[u8]) -> Result<((usize, usize), Vec<f32>), BrushParseError> {
+    let mut decoder = png::Decoder::new(bytes);
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info().map_err(BrushParseError::Png)?;
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let info = reader
+        .next_frame(&mut buffer)
+        .map_err(BrushParseError::Png)?;
+    let (m, n) = (info.height as usize, info.width as usize);
+    let channels = info.color_type.samples();
+    let brightness = buffer[..info.buffer_size()]
+        .chunks_exact(channels)
+        .map(|pixel| {
+            let pixel: Vec<f32> = pixel.iter().map(|v| *v as f32 / 255.0).collect();
+            let (value, alpha) = match info.color_type {
+                png::ColorType::Grayscale => (pixel[0], 1.0),
+                png::ColorType::GrayscaleAlpha => (pixel[0], pixel[1]),
+                png::ColorType::Rgb => ((pixel[0] + pixel[1] + pixel[2]) / 3.0, 1.0),
+                _ => ((pixel[0] + pixel[1] + pixel[2]) / 3.0, pixel[3]),
+            };
+            alpha * value + (1.0 - alpha)
+        })
+        .collect();
+    Ok(((m, n), brightness))
+}
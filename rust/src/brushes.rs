@@ -1,4 +1,7 @@
-use super::array::new_array;
+use super::array::{new_array, XorShift};
+use std::error::Error;
+use std::f32::consts::PI;
+use std::fmt;
 
 pub fn test_brushes() {
     let brush = Brush::notched_square(5, 1);
@@ -6,8 +9,56 @@ pub fn test_brushes() {
 
     brush.visualize();
     big_brush.visualize();
+
+    for brush in [brush, Brush::notched_square(4, 1), big_brush] {
+        println!("{:?}", brush.analyze());
+    }
+
+    // the Minkowski sum agrees with a brute-force dilation of random brushes
+    let mut rng = XorShift::new(42);
+    let random_brush = |rng: &mut XorShift| {
+        let (m, n) = (
+            1 + rng.next_u64() as usize % 7,
+            1 + rng.next_u64() as usize % 7,
+        );
+        let mask = (0..m * n).map(|_| rng.uniform()).collect();
+        Brush::from_f32_mask((m, n), &mask)
+    };
+    let mut failures = 0;
+    for _ in 0..1000 {
+        let (a, b) = (random_brush(&mut rng), random_brush(&mut rng));
+        if a.minkowski_sum(&b).brush != brute_force_dilation(&a, &b).brush {
+            failures += 1;
+        }
+    }
+    println!("minkowski sum mismatches: {failures}/1000");
+
+    // orientation-aware brushes
+    let ellipse = Brush::ellipse((5.0, 11.0), PI / 6.0, Rasterization::Center);
+    ellipse.visualize();
+    let rectangle = Brush::rounded_rectangle((4.0, 9.0), 1.5, 0.0, Rasterization::Majority);
+    rectangle.visualize();
+    rectangle
+        .rotate(PI / 4.0, Rasterization::Majority)
+        .visualize();
+
+    // rotations over multiples of 90° are exact (the rectangle is symmetric)
+    for rule in [
+        Rasterization::Center,
+        Rasterization::Majority,
+        Rasterization::Any,
+    ] {
+        let turned = (0..4).fold(ellipse.clone(), |brush, _| brush.rotate(PI / 2.0, rule));
+        let turned_twice = rectangle.rotate(PI / 2.0, rule).rotate(PI / 2.0, rule);
+        println!(
+            "{rule:?}: four quarter turns exact: {}, half turn exact: {}",
+            turned.brush == ellipse.brush,
+            turned_twice.mask() == rectangle.mask(),
+        );
+    }
 }
 
+#[derive(Clone)]
 pub struct Brush {
     pub brush: Vec<(i32, i32)>,
     pub shape: (usize, usize),
@@ -91,30 +142,517 @@ impl Brush {
     }
 }
 
-pub fn compute_big_brush(brush: &Brush) -> Brush {
-    let (m, n) = brush.shape;
-    let (m_, n_) = (2 * m, 2 * n);
-    let mut mask = new_array(m_ * n_, false);
-    for (i, j) in brush.brush.iter() {
-        let i_ = (m_ as i32 / 2 + *i) as usize - m % 2;
-        let j_ = (n_ as i32 / 2 + *j) as usize - n % 2;
-        apply_brush((m_, n_), &mut mask, &brush, (i_, j_), true);
+impl Brush {
+    /// Positions `pos - offset` for every brush offset (i.e. `at` for the
+    /// brush mirrored through its origin).
+    pub fn reflected_at(&self, pos: (usize, usize), shape: (usize, usize)) -> Vec<(usize, usize)> {
+        let (m, n) = pos;
+        let (size_i, size_j) = shape;
+
+        let mut new = Vec::new();
+        for (i, j) in self.brush.iter() {
+            let i = m as i32 - *i;
+            let j = n as i32 - *j;
+            if (i < 0) | (j < 0) | (size_i as i32 <= i) | (size_j as i32 <= j) {
+                continue;
+            }
+            new.push((i as usize, j as usize));
+        }
+        new
     }
 
-    let mut new_brush = Vec::new();
-    for i in 0..m_ {
-        for j in 0..n_ {
-            if mask[i * n_ + j] {
-                let i = (i as i32) - (m_ as i32) / 2;
-                let j = (j as i32) - (n_ as i32) / 2;
-                new_brush.push((i + 1, j + 1)); // yes, +1
+    /// Smallest and largest offset along both axes.
+    pub fn bounds(&self) -> ((i32, i32), (i32, i32)) {
+        let imin = self.brush.iter().map(|(i, _)| *i).min().unwrap_or(0);
+        let imax = self.brush.iter().map(|(i, _)| *i).max().unwrap_or(0);
+        let jmin = self.brush.iter().map(|(_, j)| *j).min().unwrap_or(0);
+        let jmax = self.brush.iter().map(|(_, j)| *j).max().unwrap_or(0);
+        ((imin, imax), (jmin, jmax))
+    }
+
+    /// Resample the brush mask to `shape` (nearest neighbour on pixel centers).
+    pub fn rescale(&self, shape: (usize, usize)) -> Self {
+        let (m, n) = self.shape;
+        let (m_, n_) = (shape.0.max(1), shape.1.max(1));
+        let mask = self.mask();
+        let source = |i: usize, size: usize, size_: usize| {
+            let x = (i as f32 + 0.5) * (size as f32) / (size_ as f32) - 0.5;
+            (x.round().max(0.0) as usize).min(size - 1)
+        };
+        let mut mask_ = new_array(m_ * n_, 0.0);
+        for i in 0..m_ {
+            for j in 0..n_ {
+                if mask[source(i, m, m_) * n + source(j, n, n_)] {
+                    mask_[i * n_ + j] = 1.0;
+                }
             }
         }
+        Self::from_f32_mask((m_, n_), &mask_)
     }
-    return Brush {
-        brush: new_brush,
-        shape: (m_ - 1, n_ - 1),
+
+    /// The brush for a grid downsampled by `factor`.
+    ///
+    /// The size is rounded up, so that features of the coarse design are at
+    /// least as large as the brush after upsampling.
+    pub fn downsample(&self, factor: usize) -> Self {
+        let (m, n) = self.shape;
+        let factor = factor.max(1);
+        self.rescale((m.div_ceil(factor), n.div_ceil(factor)))
+    }
+
+    /// The brush for a grid upsampled by `factor`.
+    pub fn upsample(&self, factor: usize) -> Self {
+        let (m, n) = self.shape;
+        self.rescale((m * factor, n * factor))
+    }
+
+    /// A brush from offsets, with the smallest odd shape centered on the origin.
+    pub fn from_offsets(offsets: Vec<(i32, i32)>) -> Self {
+        let mut brush = offsets;
+        brush.sort();
+        brush.dedup();
+        let ((imin, imax), (jmin, jmax)) = Brush {
+            brush: brush.clone(),
+            shape: (0, 0),
+        }
+        .bounds();
+        let m = 2 * imin.abs().max(imax.abs()) as usize + 1;
+        let n = 2 * jmin.abs().max(jmax.abs()) as usize + 1;
+        Self {
+            brush,
+            shape: (m, n),
+        }
+    }
+
+    /// All offsets of any of `brushes`.
+    pub fn union(brushes: &[Brush]) -> Self {
+        let brush = brushes
+            .iter()
+            .flat_map(|b| b.brush.iter().copied())
+            .collect();
+        Self::from_offsets(brush)
+    }
+
+    /// The brush mirrored through its origin.
+    pub fn reflected(&self) -> Self {
+        let brush = self.brush.iter().map(|(i, j)| (-i, -j)).collect();
+        Self::from_offsets(brush)
+    }
+
+    /// The Minkowski sum: all offsets `a + b` for `a` in `self` and `b` in `other`.
+    ///
+    /// Stamping the sum at a position equals stamping `other` at every offset
+    /// of `self` around it (i.e. dilating `self` by `other`).
+    pub fn minkowski_sum(&self, other: &Brush) -> Self {
+        let brush = self
+            .brush
+            .iter()
+            .flat_map(|(i, j)| other.brush.iter().map(move |(k, l)| (i + k, j + l)))
+            .collect();
+        Self::from_offsets(brush)
+    }
+}
+
+/// Which pixels a continuous shape covers when it is rasterized.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rasterization {
+    /// pixels whose center lies in the shape.
+    Center,
+    /// pixels of which more than half lies in the shape.
+    Majority,
+    /// pixels that overlap the shape.
+    Any,
+}
+
+/// Samples per pixel along each axis for `Rasterization::{Majority, Any}`.
+const SUPERSAMPLING: usize = 5;
+
+impl Rasterization {
+    /// Whether the pixel centered at `(y, x)` is covered by the shape `inside`.
+    fn covers(&self, y: f32, x: f32, inside: &impl Fn(f32, f32) -> bool) -> bool {
+        if *self == Self::Center {
+            return inside(y, x);
+        }
+        let s = SUPERSAMPLING;
+        let sample = |k: usize| (k as f32 + 0.5) / s as f32 - 0.5;
+        let count = (0..s * s)
+            .filter(|k| inside(y + sample(k / s), x + sample(k % s)))
+            .count();
+        match self {
+            Self::Majority => 2 * count > s * s,
+            _ => count > 0,
+        }
+    }
+}
+
+/// Rotate `(y, x)` (rows running down) counter-clockwise as displayed by `angle` radians.
+fn rotate_point(y: f32, x: f32, angle: f32) -> (f32, f32) {
+    let (s, c) = angle.sin_cos();
+    (y * c - x * s, x * c + y * s)
+}
+
+/// The smallest pixel count whose span centered on the shape covers `extent`.
+fn span(extent: f32) -> usize {
+    ((extent - 1e-4).ceil() as usize).max(1)
+}
+
+/// Rasterize the shape `inside(y, x)` on a grid of `shape` pixels.
+///
+/// `(y, x)` are measured in pixels from the center of the grid, which is the
+/// center pixel for odd sizes and the corner left above it for even sizes.
+fn rasterize(
+    shape: (usize, usize),
+    inside: impl Fn(f32, f32) -> bool,
+    rule: Rasterization,
+) -> Brush {
+    let (m, n) = shape;
+    let (cy, cx) = ((m as f32 - 1.0) / 2.0, (n as f32 - 1.0) / 2.0);
+    let mask = (0..m * n)
+        .map(|k| {
+            let (y, x) = ((k / n) as f32 - cy, (k % n) as f32 - cx);
+            rule.covers(y, x, &inside) as u8 as f32
+        })
+        .collect();
+    Brush::from_f32_mask(shape, &mask)
+}
+
+impl Brush {
+    /// An ellipse with full `axes` `(rows, columns)` in pixels, rotated by `angle` radians.
+    ///
+    /// Rotations are counter-clockwise as displayed (rows running down). An
+    /// unrotated ellipse of equal integer axes rasterized with
+    /// `Rasterization::Center` is the disk inscribed in its square.
+    pub fn ellipse(axes: (f32, f32), angle: f32, rule: Rasterization) -> Self {
+        let (b, a) = (axes.0 / 2.0, axes.1 / 2.0);
+        let (s, c) = angle.sin_cos();
+        let shape = (
+            span(2.0 * ((a * s).powi(2) + (b * c).powi(2)).sqrt()),
+            span(2.0 * ((a * c).powi(2) + (b * s).powi(2)).sqrt()),
+        );
+        let inside = |y: f32, x: f32| {
+            let (y, x) = rotate_point(y, x, -angle);
+            (x / a).powi(2) + (y / b).powi(2) <= 1.0
+        };
+        rasterize(shape, inside, rule)
+    }
+
+    /// A rectangle of `size` `(rows, columns)` in pixels with corners rounded
+    /// to `radius`, rotated by `angle` radians (see `ellipse`).
+    pub fn rounded_rectangle(
+        size: (f32, f32),
+        radius: f32,
+        angle: f32,
+        rule: Rasterization,
+    ) -> Self {
+        let (h, w) = (size.0 / 2.0, size.1 / 2.0);
+        let r = radius.clamp(0.0, h.min(w));
+        let (s, c) = angle.sin_cos();
+        let shape = (
+            span(2.0 * (w * s.abs() + h * c.abs())),
+            span(2.0 * (w * c.abs() + h * s.abs())),
+        );
+        let inside = |y: f32, x: f32| {
+            let (y, x) = rotate_point(y, x, -angle);
+            let dy = (y.abs() - (h - r)).max(0.0);
+            let dx = (x.abs() - (w - r)).max(0.0);
+            (y.abs() <= h) & (x.abs() <= w) & (dx * dx + dy * dy <= r * r)
+        };
+        rasterize(shape, inside, rule)
+    }
+
+    /// The brush rotated by `angle` radians around the center of its shape
+    /// (see `ellipse`), with each pixel taken as a unit square.
+    ///
+    /// Rotations over multiples of 90° are exact for every rule.
+    pub fn rotate(&self, angle: f32, rule: Rasterization) -> Self {
+        let (m, n) = self.shape;
+        let (cy, cx) = ((m as f32 - 1.0) / 2.0, (n as f32 - 1.0) / 2.0);
+        let mask = self.mask();
+        let corners = [(-0.5, -0.5), (-0.5, 0.5), (0.5, -0.5), (0.5, 0.5)];
+        let (mut ey, mut ex) = (0.0f32, 0.0f32);
+        for k in (0..m * n).filter(|k| mask[*k]) {
+            let (y, x) = ((k / n) as f32 - cy, (k % n) as f32 - cx);
+            for (dy, dx) in corners {
+                let (y, x) = rotate_point(y + dy, x + dx, angle);
+                ey = ey.max(y.abs());
+                ex = ex.max(x.abs());
+            }
+        }
+        let inside = |y: f32, x: f32| {
+            let (y, x) = rotate_point(y, x, -angle);
+            let (i, j) = ((y + cy).round(), (x + cx).round());
+            if (i < 0.0) | (j < 0.0) | (m as f32 <= i) | (n as f32 <= j) {
+                return false;
+            }
+            mask[i as usize * n + j as usize]
+        };
+        rasterize((span(2.0 * ey), span(2.0 * ex)), inside, rule)
+    }
+}
+
+/// Dilate the mask of `a` by stamping `b` at each of its pixels (for checking `minkowski_sum`).
+pub fn brute_force_dilation(a: &Brush, b: &Brush) -> Brush {
+    let (ma, na) = a.shape;
+    let (mb, nb) = b.shape;
+    let (m, n) = (ma + mb, na + nb);
+    let mut mask = new_array(m * n, false);
+    let a_mask = a.mask();
+    for k in (0..ma * na).filter(|k| a_mask[*k]) {
+        // the pixel of `a` on a grid where offset (0, 0) sits at (m / 2, n / 2)
+        let i = (k / na) as i32 - (ma / 2) as i32 + (m / 2) as i32;
+        let j = (k % na) as i32 - (na / 2) as i32 + (n / 2) as i32;
+        apply_brush((m, n), &mut mask, b, (i as usize, j as usize), true);
+    }
+    let brush = (0..m * n)
+        .filter(|k| mask[*k])
+        .map(|k| {
+            (
+                (k / n) as i32 - (m / 2) as i32,
+                (k % n) as i32 - (n / 2) as i32,
+            )
+        })
+        .collect();
+    Brush::from_offsets(brush)
+}
+
+/// Properties of a brush that the generator relies on.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BrushProperties {
+    pub size: usize,
+    pub contains_origin: bool,
+    /// whether the brush pixels are connected through shared edges.
+    pub connected: bool,
+    /// whether the brush is unchanged by a rotation over 180° (not required:
+    /// the derived brushes account for asymmetric brushes).
+    pub symmetric: bool,
+    /// number of brush pixels over the number of pixels in their convex hull.
+    pub convexity: f32,
+    /// smallest width of the brush over all directions (in pixels): the
+    /// narrowest solid feature a design can have (exact for convex brushes,
+    /// an upper bound otherwise).
+    pub min_width: f32,
+}
+
+/// Convexity below which a brush is not considered roughly convex.
+pub const MIN_CONVEXITY: f32 = 0.9;
+
+/// A brush property the generator relies on, but which the brush lacks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BrushIssue {
+    Empty,
+    MissingOrigin,
+    Disconnected,
+    NonConvex,
+    /// (voxel brushes) the brush shape has an even extent along some axis, so
+    /// the origin is not its center voxel.
+    EvenShape,
+}
+
+impl BrushIssue {
+    /// Whether no meaningful design can be generated with the brush.
+    pub fn is_fatal(&self) -> bool {
+        matches!(self, Self::Empty | Self::MissingOrigin | Self::EvenShape)
+    }
+}
+
+impl fmt::Display for BrushIssue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Empty => write!(f, "the brush is empty"),
+            Self::MissingOrigin => write!(f, "the brush does not contain its origin"),
+            Self::Disconnected => write!(f, "the brush is not connected"),
+            Self::NonConvex => write!(f, "the brush is not (roughly) convex"),
+            Self::EvenShape => write!(f, "the brush shape is not odd along every axis"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BrushError {
+    pub issues: Vec<BrushIssue>,
+}
+
+impl fmt::Display for BrushError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let issues: Vec<String> = self.issues.iter().map(|i| i.to_string()).collect();
+        write!(f, "invalid brush: {}.", issues.join(", "))
+    }
+}
+
+impl Error for BrushError {}
+
+impl BrushProperties {
+    pub fn issues(&self) -> Vec<BrushIssue> {
+        let mut issues = Vec::new();
+        if self.size == 0 {
+            issues.push(BrushIssue::Empty);
+            return issues;
+        }
+        if !self.contains_origin {
+            issues.push(BrushIssue::MissingOrigin);
+        }
+        if !self.connected {
+            issues.push(BrushIssue::Disconnected);
+        }
+        if self.convexity < MIN_CONVEXITY {
+            issues.push(BrushIssue::NonConvex);
+        }
+        issues
+    }
+}
+
+impl Brush {
+    /// Reject a brush the generator cannot use (see `BrushIssue::is_fatal`),
+    /// or return its remaining issues.
+    pub fn check(&self) -> Result<Vec<BrushIssue>, BrushError> {
+        let issues = self.analyze().issues();
+        if issues.iter().any(|issue| issue.is_fatal()) {
+            return Err(BrushError { issues });
+        }
+        Ok(issues)
+    }
+
+    pub fn analyze(&self) -> BrushProperties {
+        let mut offsets = self.brush.clone();
+        offsets.sort();
+        offsets.dedup();
+        let mut reflected: Vec<(i32, i32)> = offsets.iter().map(|(i, j)| (-i, -j)).collect();
+        reflected.sort();
+        BrushProperties {
+            size: offsets.len(),
+            contains_origin: offsets.binary_search(&(0, 0)).is_ok(),
+            connected: is_connected(&offsets),
+            symmetric: offsets == reflected,
+            convexity: offsets.len() as f32 / convex_hull_size(&offsets).max(1) as f32,
+            min_width: min_width(&offsets),
+        }
+    }
+}
+
+/// Whether the (sorted) offsets are connected through shared edges.
+fn is_connected(offsets: &[(i32, i32)]) -> bool {
+    if offsets.is_empty() {
+        return true;
+    }
+    let mut seen = vec![false; offsets.len()];
+    let mut stack = vec![0];
+    seen[0] = true;
+    while let Some(k) = stack.pop() {
+        let (i, j) = offsets[k];
+        for neighbour in [(i - 1, j), (i + 1, j), (i, j - 1), (i, j + 1)] {
+            if let Ok(n) = offsets.binary_search(&neighbour) {
+                if !seen[n] {
+                    seen[n] = true;
+                    stack.push(n);
+                }
+            }
+        }
+    }
+    seen.iter().all(|s| *s)
+}
+
+/// Number of grid points in the convex hull of the (sorted) offsets.
+fn convex_hull_size(offsets: &[(i32, i32)]) -> usize {
+    let cross = |o: (i32, i32), a: (i32, i32), b: (i32, i32)| {
+        (a.0 - o.0) as i64 * (b.1 - o.1) as i64 - (a.1 - o.1) as i64 * (b.0 - o.0) as i64
     };
+    // Andrew's monotone chain, counter-clockwise
+    let mut hull: Vec<(i32, i32)> = Vec::new();
+    for pass in [offsets.to_vec(), offsets.iter().rev().copied().collect()] {
+        let start = hull.len();
+        for p in pass.into_iter() {
+            while (hull.len() >= start + 2)
+                && (cross(hull[hull.len() - 2], hull[hull.len() - 1], p) <= 0)
+            {
+                hull.pop();
+            }
+            hull.push(p);
+        }
+        hull.pop();
+    }
+    let ((imin, imax), (jmin, jmax)) = Brush {
+        brush: offsets.to_vec(),
+        shape: (0, 0),
+    }
+    .bounds();
+    let mut count = 0;
+    for i in imin..=imax {
+        for j in jmin..=jmax {
+            let p = (i, j);
+            let inside = if hull.len() < 3 {
+                // a point or a line segment
+                hull.iter()
+                    .all(|a| hull.iter().all(|b| cross(*a, *b, p) == 0))
+            } else {
+                (0..hull.len()).all(|k| cross(hull[k], hull[(k + 1) % hull.len()], p) >= 0)
+            };
+            count += inside as usize;
+        }
+    }
+    count
+}
+
+/// Smallest extent of the brush pixels (as unit squares) over all directions.
+fn min_width(offsets: &[(i32, i32)]) -> f32 {
+    if offsets.is_empty() {
+        return 0.0;
+    }
+    let mut min_width = f32::INFINITY;
+    for degrees in 0..180 {
+        let angle = (degrees as f32).to_radians();
+        let (u, v) = (angle.cos(), angle.sin());
+        let projections = offsets.iter().map(|(i, j)| *i as f32 * u + *j as f32 * v);
+        let min = projections.clone().fold(f32::INFINITY, f32::min);
+        let max = projections.fold(f32::NEG_INFINITY, f32::max);
+        min_width = min_width.min(max - min + u.abs() + v.abs());
+    }
+    min_width
+}
+
+/// The touches whose brush overlaps the brush of a touch at the origin.
+pub fn compute_big_brush(brush: &Brush) -> Brush {
+    compute_big_brush_pair(brush, brush)
+}
+
+/// The solid touches whose `solid_brush` overlaps `void_brush` at the origin.
+///
+/// The solid touches overlapping a solid touch at the origin are given by the
+/// reflection of this brush.
+pub fn compute_big_brush_pair(void_brush: &Brush, solid_brush: &Brush) -> Brush {
+    void_brush.minkowski_sum(&solid_brush.reflected())
+}
+
+/// The touches that can become free after a touch at the origin.
+///
+/// A touch invalidates the opposite touches in its big brush; the pixels those
+/// covered may become required, which frees the touches overlapping them. This
+/// is the exact (non-square) reach that `compute_very_big_square_brush`
+/// approximates.
+pub fn compute_very_big_brush(brush: &Brush) -> Brush {
+    compute_very_big_brush_pair(brush, brush)
+}
+
+/// Like `compute_very_big_brush`, for distinct void and solid brushes (the
+/// result holds for touches on either side).
+pub fn compute_very_big_brush_pair(void_brush: &Brush, solid_brush: &Brush) -> Brush {
+    let big_brush = compute_big_brush_pair(void_brush, solid_brush);
+    big_brush.minkowski_sum(&big_brush.reflected())
+}
+
+/// Like `compute_very_big_brush`, for a void touch with `void_brush` when the
+/// other touches may use any of `void_brushes` and `solid_brushes`.
+pub fn compute_very_big_brush_set(
+    void_brush: &Brush,
+    void_brushes: &[Brush],
+    solid_brushes: &[Brush],
+) -> Brush {
+    // the solid touches overlapping the void touch, the pixels they covered,
+    // and the void touches covering those pixels
+    let solid_reach: Vec<Brush> = solid_brushes.iter().map(compute_big_brush).collect();
+    let void_reach: Vec<Brush> = void_brushes.iter().map(|b| b.reflected()).collect();
+    void_brush
+        .minkowski_sum(&Brush::union(&solid_reach))
+        .minkowski_sum(&Brush::union(&void_reach))
 }
 
 pub fn compute_very_big_square_brush(brush: &Brush) -> Brush {
@@ -142,7 +680,7 @@ pub fn compute_big_square_brush(brush: &Brush) -> Brush {
     let mut new_brush = Vec::new();
     for i in 0..m_ {
         for j in 0..n_ {
-            new_brush.push((i as i32 - m as i32, j as i32 - m as i32));
+            new_brush.push((i as i32 - m as i32, j as i32 - n as i32));
         }
     }
     return Brush {
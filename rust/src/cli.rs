@@ -0,0 +1,230 @@
+use super::array::parse_f32;
+use super::brush_io::{read_brush, write_brush, write_mask, BrushFileError, Threshold};
+use super::brushes::{Brush, BrushError};
+use super::connectivity::{complete_connected_design, ConnectivityRules};
+use super::design::{Design, InactivePolicy};
+use super::design_rules::{DesignRuleError, DesignRules};
+use super::generator::{generate_feasible_design_with_brushes, GeneratorError, GeneratorOptions};
+use super::layers::Layer;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+pub const USAGE: &str = "usage:
+    inverse_design_rs brush show <brush> [--threshold <level>] [--invert]
+    inverse_design_rs brush convert <brush> <output> [--threshold <level>] [--invert]
+    inverse_design_rs rules <rules> [--void-brush <brush>] [--solid-brush <brush>]
+    inverse_design_rs generate <latent.bin> <rows>x<columns> (--brush <brush> | <rules>) [--output <design>]
+        [--provenance <image.png>] [--iterations <image.png>] [--connected]
+
+brushes are read from and written to .txt (ASCII art), .pbm, .pgm or .png files;
+designs are written the same way, with solid pixels dark.
+--provenance colors every pixel by the kind of touch that assigned it and
+--iterations by when it was assigned (see `Provenance`). --connected forbids
+solid islands and enclosed voids (see `ConnectivityRules`).
+
+<rules> are --min-width <length> --min-gap <length> --pitch <length per pixel>
+[--shape circle|notched-square], in any (but the same) unit, e.g. nanometres.";
+
+#[derive(Debug)]
+pub enum CliError {
+    Usage(String),
+    BrushFile(BrushFileError),
+    Brush(BrushError),
+    DesignRules(DesignRuleError),
+    Generator(GeneratorError),
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Usage(message) => write!(f, "{message}\n\n{USAGE}"),
+            Self::BrushFile(error) => write!(f, "{error}"),
+            Self::Brush(error) => write!(f, "{error}"),
+            Self::DesignRules(error) => write!(f, "{error}"),
+            Self::Generator(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+impl Error for CliError {}
+
+impl From<BrushFileError> for CliError {
+    fn from(error: BrushFileError) -> Self {
+        Self::BrushFile(error)
+    }
+}
+
+impl From<BrushError> for CliError {
+    fn from(error: BrushError) -> Self {
+        Self::Brush(error)
+    }
+}
+
+impl From<DesignRuleError> for CliError {
+    fn from(error: DesignRuleError) -> Self {
+        Self::DesignRules(error)
+    }
+}
+
+impl From<GeneratorError> for CliError {
+    fn from(error: GeneratorError) -> Self {
+        Self::Generator(error)
+    }
+}
+
+/// Run the command line interface with `args` (without the program name).
+pub fn run(args: &[String]) -> Result<(), CliError> {
+    let (positional, options) = parse_args(args)?;
+    let positional: Vec<&str> = positional.iter().map(|s| s.as_str()).collect();
+    let threshold = threshold(&options)?;
+    match positional.as_slice() {
+        ["brush", "show", path] => {
+            let brush = read_brush(path, &threshold)?;
+            check_brush(&brush)?;
+            brush.visualize();
+            print!("{}", brush.to_ascii());
+        }
+        ["brush", "convert", input, output] => {
+            let brush = read_brush(input, &threshold)?;
+            check_brush(&brush)?;
+            write_brush(output, &brush)?;
+        }
+        ["rules"] => {
+            let rules = design_rules(&options)?
+                .ok_or_else(|| CliError::Usage("missing design rules".to_string()))?;
+            println!("{}", rules.enforced()?);
+            if let Some(path) = options.get("void-brush") {
+                write_brush(path, &rules.void_brush()?)?;
+            }
+            if let Some(path) = options.get("solid-brush") {
+                write_brush(path, &rules.solid_brush()?)?;
+            }
+        }
+        ["generate", latent, shape] => {
+            let shape = parse_shape(shape)?;
+            let (void_brush, solid_brush) = match (options.get("brush"), design_rules(&options)?) {
+                (Some(path), None) => {
+                    let brush = read_brush(path, &threshold)?;
+                    (brush.clone(), brush)
+                }
+                (None, Some(rules)) => (rules.void_brush()?, rules.solid_brush()?),
+                _ => {
+                    let message = "expected either --brush or design rules";
+                    return Err(CliError::Usage(message.to_string()));
+                }
+            };
+            check_brush(&void_brush)?;
+            check_brush(&solid_brush)?;
+            let bytes = std::fs::read(latent).map_err(BrushFileError::Io)?;
+            let latent_t = parse_f32(&bytes);
+            let options_ = GeneratorOptions::default();
+            let design = if options.contains_key("connected") {
+                let (m, n) = shape;
+                let active = vec![true; m * n];
+                let policy = InactivePolicy::Forbidden;
+                let design = Design::with_brushes(shape, void_brush, solid_brush, active, policy);
+                let rules = ConnectivityRules::default();
+                let (design, _) = complete_connected_design(design, &latent_t, &rules, &options_)?;
+                design
+            } else {
+                let (design, _) = generate_feasible_design_with_brushes(
+                    shape,
+                    &latent_t,
+                    void_brush,
+                    solid_brush,
+                    &options_,
+                )?;
+                design
+            };
+            match options.get("output") {
+                Some(path) => write_mask(path, shape, &design[Layer::Solid])?,
+                None => design.visualize(),
+            }
+            if let Some(path) = options.get("provenance") {
+                design.provenance.write_kind_image(path, shape)?;
+            }
+            if let Some(path) = options.get("iterations") {
+                design.provenance.write_iteration_image(path, shape)?;
+            }
+        }
+        _ => return Err(CliError::Usage("unknown command".to_string())),
+    }
+    Ok(())
+}
+
+/// Reject brushes the generator cannot use (see `BrushIssue::is_fatal`) and
+/// warn about the other issues of `Brush::analyze`.
+fn check_brush(brush: &Brush) -> Result<(), CliError> {
+    for issue in brush.check()?.iter() {
+        eprintln!("warning: {issue}; the generated designs may not respect the brush.");
+    }
+    Ok(())
+}
+
+/// Split `args` into positional arguments and `--name value` options
+/// (`--invert` and `--connected` take no value).
+fn parse_args(args: &[String]) -> Result<(Vec<String>, HashMap<String, String>), CliError> {
+    let mut positional = Vec::new();
+    let mut options = HashMap::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let Some(name) = arg.strip_prefix("--") else {
+            positional.push(arg.clone());
+            continue;
+        };
+        let value = match name {
+            "invert" | "connected" => "true".to_string(),
+            _ => args
+                .next()
+                .ok_or_else(|| CliError::Usage(format!("missing value for --{name}")))?
+                .clone(),
+        };
+        options.insert(name.to_string(), value);
+    }
+    Ok((positional, options))
+}
+
+fn threshold(options: &HashMap<String, String>) -> Result<Threshold, CliError> {
+    let mut threshold = Threshold::default();
+    if let Some(level) = options.get("threshold") {
+        threshold.level = level
+            .parse()
+            .map_err(|_| CliError::Usage(format!("invalid threshold '{level}'")))?;
+    }
+    threshold.invert = options.contains_key("invert");
+    Ok(threshold)
+}
+
+/// The design rules in `options`, if any are given (then all lengths are required).
+fn design_rules(options: &HashMap<String, String>) -> Result<Option<DesignRules>, CliError> {
+    let names = ["min-width", "min-gap", "pitch"];
+    if !names.iter().any(|name| options.contains_key(*name)) {
+        return Ok(None);
+    }
+    let mut lengths = Vec::new();
+    for name in names {
+        let value = options
+            .get(name)
+            .ok_or_else(|| CliError::Usage(format!("missing option --{name}")))?;
+        let length: f32 = value
+            .parse()
+            .map_err(|_| CliError::Usage(format!("invalid --{name} '{value}'")))?;
+        lengths.push(length);
+    }
+    let mut rules = DesignRules::new(lengths[0], lengths[1], lengths[2]);
+    if let Some(shape) = options.get("shape") {
+        rules.shape = shape.parse()?;
+    }
+    rules.check()?;
+    Ok(Some(rules))
+}
+
+fn parse_shape(shape: &str) -> Result<(usize, usize), CliError> {
+    let invalid = || CliError::Usage(format!("invalid shape '{shape}'"));
+    let (m, n) = shape.split_once('x').ok_or_else(invalid)?;
+    Ok((
+        m.parse().map_err(|_| invalid())?,
+        n.parse().map_err(|_| invalid())?,
+    ))
+}
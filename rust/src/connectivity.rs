@@ -0,0 +1,421 @@
+use super::array::read_f32;
+use super::brushes::Brush;
+use super::debug::Profiler;
+use super::design::{Design, InactivePolicy};
+use super::generator::{GeneratorError, GeneratorOptions, GeneratorReport, UnresolvedStrategy};
+use super::layers::Layer;
+use super::metrics::label_components;
+use super::warm_start::complete_seeded_design;
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+pub fn test_connectivity() {
+    let seed = 42;
+    let (m, n) = (60, 60);
+    let brush = Brush::notched_square(5, 1);
+    // the noise of the top left corner of a larger latent.
+    let noise = read_f32(&format!("latent_t_{seed}_128x128.bin"));
+    // a solid disk in a void ring in a solid ring: an island in an enclosed void.
+    let latent_t: Vec<f32> = (0..m * n)
+        .map(|k| {
+            let l = noise[(k / n) * 128 + k % n];
+            let (i, j) = ((k / n) as f32 - 29.5, (k % n) as f32 - 29.5);
+            let r = (i * i + j * j).sqrt();
+            match r {
+                r if r < 6.0 => 1.0 + 0.1 * l,
+                r if r < 14.0 => -1.0 + 0.1 * l,
+                r if r < 22.0 => 1.0 + 0.1 * l,
+                _ => -1.0 + 0.1 * l,
+            }
+        })
+        .collect();
+    let rules = ConnectivityRules::default();
+    let design = Design::new((m, n), brush);
+    let (unconstrained, _) = complete_seeded_design(
+        design.clone(),
+        &Vec::new(),
+        &Vec::new(),
+        &latent_t,
+        &GeneratorOptions::default(),
+    )
+    .unwrap();
+    unconstrained.visualize();
+    let violations = unconstrained.check_connectivity(&rules);
+    println!("violations without the rules: {}", violations.len());
+    for violation in violations.iter() {
+        println!("{violation}");
+    }
+    let (connected, report) =
+        complete_connected_design(design, &latent_t, &rules, &GeneratorOptions::default()).unwrap();
+    connected.visualize();
+    println!(
+        "refused touches: {}, rounds: {}, violations after generation: {}, feasible: {}",
+        report.generator.refused_touches,
+        report.rounds,
+        connected.check_connectivity(&rules).len(),
+        connected.is_feasible()
+    );
+
+    // a void seeded inside a ring of inactive pixels that act as solid cannot be connected
+    let active: Vec<bool> = (0..m * n)
+        .map(|k| {
+            let (i, j) = ((k / n) as f32 - 29.5, (k % n) as f32 - 29.5);
+            let r = (i * i + j * j).sqrt();
+            !(10.0..12.0).contains(&r)
+        })
+        .collect();
+    let policy = InactivePolicy::Solid;
+    let brush = Brush::notched_square(5, 1);
+    let mut design = Design::with_brushes((m, n), brush.clone(), brush, active, policy);
+    design.stamp_void_touch((30, 30));
+    match complete_connected_design(design, &latent_t, &rules, &GeneratorOptions::default()) {
+        Ok((design, _)) => println!(
+            "connected: {}",
+            design.check_connectivity(&rules).is_empty()
+        ),
+        Err(error) => println!("{error}"),
+    }
+}
+
+/// Which components a design may not have.
+///
+/// A component (4-connected, see `label_components`) is anchored when it
+/// touches the edge of the grid, or inactive pixels that do not act as the
+/// other side (see `InactivePolicy`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConnectivityRules {
+    /// forbid solid components that are not anchored (e.g. floating membranes).
+    pub forbid_solid_islands: bool,
+    /// forbid void components that are not anchored (e.g. unreachable by an etch).
+    pub forbid_enclosed_voids: bool,
+    /// the maximum number of times the design is regenerated to repair it
+    /// (see `complete_connected_design`).
+    pub max_rounds: usize,
+}
+
+impl ConnectivityRules {
+    /// The same rules with void and solid swapped (see `Design::invert`).
+    pub fn opposite(&self) -> Self {
+        Self {
+            forbid_solid_islands: self.forbid_enclosed_voids,
+            forbid_enclosed_voids: self.forbid_solid_islands,
+            max_rounds: self.max_rounds,
+        }
+    }
+}
+
+impl Default for ConnectivityRules {
+    fn default() -> Self {
+        Self {
+            forbid_solid_islands: true,
+            forbid_enclosed_voids: true,
+            max_rounds: 10,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ConnectivityReport {
+    /// number of times the design was regenerated after the first pass.
+    pub rounds: usize,
+    /// number of components that were removed or connected (over all rounds).
+    pub repaired_components: usize,
+    /// the report of the last generator pass.
+    pub generator: GeneratorReport,
+}
+
+/// Complete an (empty) design that obeys `rules`.
+///
+/// The generator refuses every touch that would strand pixels (see
+/// `GeneratorOptions::connectivity`), and backtracks from the dead ends that
+/// leaves (see `UnresolvedStrategy::Backtrack`). Components that break the rules anyway,
+/// e.g. because required pixels closed them off before the first touch, are
+/// repaired by regenerating the design: the latent is modified to either
+/// remove them or connect them to an anchored component along the path that
+/// overrides the least latent, whichever overrides less. The modified pixels
+/// are given the largest latent magnitude, so that the generator touches them
+/// first. Fails with `GeneratorError::Disconnected` if violations are left
+/// after `rules.max_rounds` rounds.
+pub fn complete_connected_design(
+    design: Design,
+    latent_t: &[f32],
+    rules: &ConnectivityRules,
+    options: &GeneratorOptions,
+) -> Result<(Design, ConnectivityReport), GeneratorError> {
+    let profiler = Profiler::start("complete_connected_design");
+    let empty = design;
+    let options = GeneratorOptions {
+        unresolved_strategy: UnresolvedStrategy::Backtrack,
+        connectivity: Some(rules.clone()),
+        ..options.clone()
+    };
+    let mut latent_t = latent_t.to_vec();
+    let mut report = ConnectivityReport::default();
+    loop {
+        let (design, generator) =
+            complete_seeded_design(empty.clone(), &Vec::new(), &Vec::new(), &latent_t, &options)?;
+        report.generator = generator;
+        let violations = design.check_connectivity(rules);
+        if violations.is_empty() {
+            profiler.stop();
+            return Ok((design, report));
+        }
+        if report.rounds >= rules.max_rounds {
+            profiler.stop();
+            return Err(GeneratorError::Disconnected { violations });
+        }
+        if options.verbose {
+            println!(
+                "round {}: {} connectivity violations.",
+                report.rounds,
+                violations.len()
+            );
+        }
+        report.repaired_components += repair_latent(&design, &mut latent_t, rules);
+        report.rounds += 1;
+    }
+}
+
+impl Design {
+    /// The components of the `solid` (or void) pixels that are not anchored
+    /// (see `ConnectivityRules`), as lists of pixel indices.
+    pub fn floating_components(&self, solid: bool) -> Vec<Vec<usize>> {
+        let layer = if solid { Layer::Solid } else { Layer::Void };
+        let (labels, count) = label_components(self.shape, &self[layer]);
+        let mut anchored = vec![false; count];
+        let mut components = vec![Vec::new(); count];
+        for (k, label) in labels.iter().enumerate() {
+            let Some(label) = label else {
+                continue;
+            };
+            components[*label].push(k);
+            anchored[*label] |= self.is_anchor(k, solid);
+        }
+        components
+            .into_iter()
+            .zip(anchored)
+            .filter(|(_, anchored)| !anchored)
+            .map(|(component, _)| component)
+            .collect()
+    }
+
+    /// The number of pixels that can no longer end up in an anchored component
+    /// of their side: solid (or void) pixels that only connect to an anchor
+    /// through pixels of the other side.
+    ///
+    /// Assigning more pixels never lowers it; once every active pixel is
+    /// assigned, it is zero exactly when `check_connectivity` finds nothing.
+    pub fn stranded_pixels(&self, rules: &ConnectivityRules) -> usize {
+        let mut stranded = 0;
+        for solid in [true, false] {
+            let forbidden = if solid {
+                rules.forbid_solid_islands
+            } else {
+                rules.forbid_enclosed_voids
+            };
+            if !forbidden {
+                continue;
+            }
+            let (m, n) = self.shape;
+            let (layer, other) = if solid {
+                (&self[Layer::Solid], &self[Layer::Void])
+            } else {
+                (&self[Layer::Void], &self[Layer::Solid])
+            };
+            let open = |k: usize| self.active[k] & !other[k];
+            let mut reached = vec![false; m * n];
+            let mut queue: Vec<usize> = (0..m * n)
+                .filter(|k| open(*k) && self.is_anchor(*k, solid))
+                .collect();
+            for k in queue.iter() {
+                reached[*k] = true;
+            }
+            while let Some(k) = queue.pop() {
+                let (i, j) = (k / n, k % n);
+                let neighbours = [
+                    (i > 0, k.wrapping_sub(n)),
+                    (i + 1 < m, k + n),
+                    (j > 0, k.wrapping_sub(1)),
+                    (j + 1 < n, k + 1),
+                ];
+                for (inside, p) in neighbours {
+                    if inside && !reached[p] && open(p) {
+                        reached[p] = true;
+                        queue.push(p);
+                    }
+                }
+            }
+            stranded += (0..m * n).filter(|k| layer[*k] & !reached[*k]).count();
+        }
+        stranded
+    }
+
+    /// Whether a `solid` (or void) pixel at `k` anchors its component: it is
+    /// on the edge of the grid, or next to inactive pixels that do not act as
+    /// the other side.
+    fn is_anchor(&self, k: usize, solid: bool) -> bool {
+        let (m, n) = self.shape;
+        let (i, j) = (k / n, k % n);
+        if (i == 0) | (j == 0) | (i + 1 == m) | (j + 1 == n) {
+            return true;
+        }
+        let opposite = if solid {
+            InactivePolicy::Void
+        } else {
+            InactivePolicy::Solid
+        };
+        // pixels off the edge have all four neighbours
+        let neighbours = [k - n, k + n, k - 1, k + 1];
+        (self.inactive_policy != opposite) & neighbours.iter().any(|p| !self.active[*p])
+    }
+}
+
+/// Modify the latent to remove or connect the floating components of `design`.
+///
+/// Returns the number of components handled.
+fn repair_latent(design: &Design, latent_t: &mut [f32], rules: &ConnectivityRules) -> usize {
+    let peak = latent_t.iter().fold(0.0f32, |a, l| a.max(l.abs())) + 1.0;
+    let mut repaired = 0;
+    for solid in [true, false] {
+        let forbidden = if solid {
+            rules.forbid_solid_islands
+        } else {
+            rules.forbid_enclosed_voids
+        };
+        if !forbidden {
+            continue;
+        }
+        // the latent with the sign of this side, solid for islands and void for holes.
+        let sign = if solid { 1.0 } else { -1.0 };
+        let brushes = if solid {
+            &design.solid_brushes
+        } else {
+            &design.brushes
+        };
+        let width = brushes
+            .iter()
+            .map(|b| b.shape.0.max(b.shape.1))
+            .max()
+            .unwrap() as f32;
+        let floating = design.floating_components(solid);
+        let mask = if solid {
+            &design[Layer::Solid]
+        } else {
+            &design[Layer::Void]
+        };
+        let mut anchors = mask.clone();
+        for component in floating.iter() {
+            for k in component.iter() {
+                anchors[*k] = false;
+            }
+        }
+        for component in floating.iter() {
+            let removal_cost: f32 = component
+                .iter()
+                .map(|k| (sign * latent_t[*k]).max(0.0))
+                .sum();
+            let bridge = cheapest_bridge(design, &anchors, component, latent_t, sign, peak);
+            match bridge {
+                Some((cost, path)) if cost * width < removal_cost => {
+                    for k in path {
+                        latent_t[k] = sign * peak;
+                    }
+                }
+                _ => {
+                    for k in component.iter() {
+                        latent_t[*k] = -sign * peak;
+                    }
+                }
+            }
+            repaired += 1;
+        }
+    }
+    repaired
+}
+
+/// The cheapest path of active pixels from `component` to an anchored pixel
+/// (one of `anchors` or on the edge of the grid): its cost, i.e. the latent it
+/// overrides, and its pixels outside the component.
+fn cheapest_bridge(
+    design: &Design,
+    anchors: &[bool],
+    component: &[usize],
+    latent_t: &[f32],
+    sign: f32,
+    peak: f32,
+) -> Option<(f32, Vec<usize>)> {
+    let (m, n) = design.shape;
+    // a small cost per pixel prefers short paths between equally cheap ones.
+    let step_cost = |k: usize| (-sign * latent_t[k]).max(0.0) + 1e-3 * peak;
+    let mut cost = vec![f32::INFINITY; m * n];
+    let mut previous = vec![usize::MAX; m * n];
+    let mut queue = BinaryHeap::new();
+    for k in component.iter() {
+        cost[*k] = 0.0;
+        queue.push(Step {
+            cost: 0.0,
+            pixel: *k,
+        });
+    }
+    while let Some(Step { cost: c, pixel: k }) = queue.pop() {
+        if c > cost[k] {
+            continue;
+        }
+        let (i, j) = (k / n, k % n);
+        let edge = (i == 0) | (j == 0) | (i + 1 == m) | (j + 1 == n);
+        if anchors[k] | edge {
+            let mut path = Vec::new();
+            let mut p = k;
+            while cost[p] > 0.0 {
+                path.push(p);
+                p = previous[p];
+            }
+            return Some((c, path));
+        }
+        let neighbours = [
+            (i > 0, k.wrapping_sub(n)),
+            (i + 1 < m, k + n),
+            (j > 0, k.wrapping_sub(1)),
+            (j + 1 < n, k + 1),
+        ];
+        for (inside, p) in neighbours {
+            if !inside || !design.active[p] {
+                continue;
+            }
+            let cp = c + step_cost(p);
+            if cp < cost[p] {
+                cost[p] = cp;
+                previous[p] = k;
+                queue.push(Step { cost: cp, pixel: p });
+            }
+        }
+    }
+    None
+}
+
+/// A pixel in the queue of `cheapest_bridge`, ordered by increasing cost.
+#[derive(Debug, Clone, Copy)]
+struct Step {
+    cost: f32,
+    pixel: usize,
+}
+
+impl Ord for Step {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.cost.total_cmp(&self.cost)
+    }
+}
+
+impl PartialOrd for Step {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Step {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Step {}
@@ -1,12 +1,15 @@
-use super::array::new_array;
 use super::brushes::{
-    apply_brush, compute_big_brush, compute_very_big_square_brush, multi_apply_brush,
-    multi_apply_touch, Brush,
+    compute_big_brush_pair, compute_very_big_brush_set, Brush, BrushError, BrushIssue,
 };
 use super::debug::Profiler;
-use rayon::iter::ParallelBridge;
-use rayon::prelude::ParallelIterator;
+use super::generator::GeneratorError;
+use super::grid::Grid;
+use super::layers::{Layer, Layers, VOID_BRUSH_STAMP, VOID_TOUCH_STAMP};
+use super::provenance::{PixelOrigin, Provenance};
+use super::stencil::{Stencil, Stencils};
+use rayon::prelude::{IntoParallelIterator, ParallelIterator};
 use std::mem::swap;
+use std::ops::{Index, IndexMut};
 
 pub fn test_design() {
     let shape: (usize, usize) = (6, 8);
@@ -59,171 +62,834 @@ pub fn test_design() {
     design.visualize();
 }
 
+/// How brushes may overlap the inactive pixels of a design region.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InactivePolicy {
+    /// inactive pixels act as fixed solid: only solid brushes may overlap them.
+    Solid,
+    /// inactive pixels act as fixed void: only void brushes may overlap them.
+    Void,
+    /// no brush may overlap inactive pixels.
+    Forbidden,
+}
+
+impl InactivePolicy {
+    /// The same policy with void and solid swapped.
+    pub fn opposite(self) -> Self {
+        match self {
+            Self::Solid => Self::Void,
+            Self::Void => Self::Solid,
+            Self::Forbidden => Self::Forbidden,
+        }
+    }
+}
+
+/// The pixels a touch leaves required, and the touches that could resolve them.
+pub type TouchEffects = (Vec<(usize, usize)>, Vec<(usize, usize)>);
+
+/// The kind of touch that assigned a pixel (see `PixelOrigin`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TouchKind {
+    /// a touch selected from the latent.
+    Primary,
+    /// a touch taken to resolve the required pixels of another touch.
+    Resolving,
+    /// a free touch, taken because it could not conflict with anything.
+    Free,
+}
+
+#[derive(Clone)]
 pub struct Design {
+    /// the planar `(rows, columns)` view of `grid`.
     pub shape: (usize, usize),
-    pub brush: Brush,
+    /// the grid of the design: planar, or e.g. a voxel grid (see `voxels`).
+    pub grid: Grid,
+    /// the brushes of the void touches (usually just one).
+    pub brushes: Vec<Brush>,
+    /// the brushes of the solid touches (usually the same as `brushes`).
+    pub solid_brushes: Vec<Brush>,
+    /// the index in `brushes` and `solid_brushes` of the brush of the touches at each pixel.
+    pub brush_index: Vec<usize>,
+    /// the solid touches that may overlap a void touch at the origin.
     pub big_brush: Brush,
+    /// the touches that may become free after a touch at the origin.
     pub very_big_brush: Brush,
+    /// the brushes of the void touches precomputed for `shape`, per brush.
+    pub stencils: Vec<Stencils>,
+    /// the brushes of the solid touches (swapped with `stencils` by `invert`).
+    pub solid_stencils: Vec<Stencils>,
 
-    pub void: Vec<bool>,  /*  1 */
-    pub solid: Vec<bool>, /*  2 */
+    /// the boolean layers, indexed by `Layer` (also available as `design[layer]`).
+    pub layers: Layers,
 
-    pub void_pixel_impossible: Vec<bool>, /*  3 */
-    pub void_pixel_existing: Vec<bool>,   /*  4 */
-    pub void_pixel_required: Vec<bool>,   /*  6 */
+    /// pixels that belong to the design region; inactive pixels are never assigned.
+    pub active: Vec<bool>,
+    pub inactive_policy: InactivePolicy,
+    any_inactive: bool,
 
-    pub solid_pixel_impossible: Vec<bool>, /*  3 */
-    pub solid_pixel_existing: Vec<bool>,   /*  4 */
-    pub solid_pixel_required: Vec<bool>,   /*  6 */
+    /// number of valid void touches covering each pixel (in `reflected_brush.at(pixel)`).
+    pub void_touch_coverage: Vec<u32>,
+    /// number of valid solid touches covering each pixel.
+    pub solid_touch_coverage: Vec<u32>,
 
-    pub void_touch_required: Vec<bool>, /*  7 */
-    pub void_touch_invalid: Vec<bool>,  /*  8 */
-    pub void_touch_existing: Vec<bool>, /*  9 */
+    /// the step and the kind of touch that first assigned each pixel.
+    pub provenance: Provenance,
+
+    /// flagged void pixels that might still be required.
+    void_pixel_required_pending: Vec<(usize, usize)>,
+    /// flagged solid pixels that might still be required.
+    solid_pixel_required_pending: Vec<(usize, usize)>,
+
+    /// the state to restore on `Design::rollback`, while recording.
+    journal: Option<Journal>,
+}
 
-    pub solid_touch_required: Vec<bool>, /*  7 */
-    pub solid_touch_invalid: Vec<bool>,  /*  8 */
-    pub solid_touch_existing: Vec<bool>, /*  9 */
+/// The state of a `Design` at its last checkpoint, saved for the pixels that
+/// may have changed since (see `Design::checkpoint`).
+#[derive(Clone)]
+struct Journal {
+    /// the box around a void touch that adding it may change: its brush, the
+    /// brushes of the solid touches it invalidates and of the free touches it
+    /// takes.
+    footprint: Stencil,
+    recording: bool,
+    /// the `(start, length)` runs of saved pixels, in the order they were saved.
+    runs: Vec<(usize, usize)>,
+    /// per run, every layer (in the order of `Layer::ALL`) over the run.
+    layers: Vec<bool>,
+    /// per run, the void and then the solid touch coverage over the run.
+    coverage: Vec<u32>,
+    origins: Vec<Option<PixelOrigin>>,
+    void_pending: Vec<(usize, usize)>,
+    solid_pending: Vec<(usize, usize)>,
+    iterations: usize,
 }
 
 impl Design {
+    /// A design for `brush`, which is checked with `Brush::analyze`.
+    ///
+    /// Panics for brushes the generator cannot use (empty, or without their
+    /// origin). Use `Design::try_with_brushes` to get an error and the other
+    /// issues of the brush instead, or `Design::try_new` to reject those as well.
     pub fn new(shape: (usize, usize), brush: Brush) -> Self {
         let (size_x, size_y) = shape;
-        let big_brush = compute_big_brush(&brush);
-        let very_big_brush = compute_very_big_square_brush(&brush);
+        let active = vec![true; size_x * size_y];
+        Self::with_active_region(shape, brush, active, InactivePolicy::Forbidden)
+    }
 
-        return Self {
-            shape: (size_x, size_y),
-            brush,
+    /// Like `Design::new`, but rejects every brush with issues.
+    pub fn try_new(shape: (usize, usize), brush: Brush) -> Result<Self, BrushError> {
+        let issues = brush.analyze().issues();
+        if !issues.is_empty() {
+            return Err(BrushError { issues });
+        }
+        Ok(Self::new(shape, brush))
+    }
+
+    /// A design restricted to the pixels marked in `active`.
+    ///
+    /// Touches at inactive pixels are invalid, as are the touches whose brush
+    /// overlaps inactive pixels where `policy` does not allow it. Inactive pixels
+    /// behave as existing pixels of the side allowed to overlap them, but are
+    /// never set in the `Void` or `Solid` layers.
+    pub fn with_active_region(
+        shape: (usize, usize),
+        brush: Brush,
+        active: Vec<bool>,
+        policy: InactivePolicy,
+    ) -> Self {
+        Self::with_brushes(shape, brush.clone(), brush, active, policy)
+    }
+
+    /// Like `Design::with_active_region`, with separate brushes for the void
+    /// and the solid touches, e.g. to enforce a minimum gap that differs from
+    /// the minimum width (see `DesignRules`).
+    pub fn with_brushes(
+        shape: (usize, usize),
+        void_brush: Brush,
+        solid_brush: Brush,
+        active: Vec<bool>,
+        policy: InactivePolicy,
+    ) -> Self {
+        let (m, n) = shape;
+        let brush_index = vec![0; m * n];
+        Self::with_brush_map(
+            shape,
+            vec![void_brush],
+            vec![solid_brush],
+            brush_index,
+            active,
+            policy,
+        )
+    }
+
+    /// Like `Design::with_brushes`, but without panicking (see
+    /// `Design::try_with_brush_map`).
+    pub fn try_with_brushes(
+        shape: (usize, usize),
+        void_brush: Brush,
+        solid_brush: Brush,
+        active: Vec<bool>,
+        policy: InactivePolicy,
+    ) -> Result<(Self, Vec<BrushIssue>), GeneratorError> {
+        let (m, n) = shape;
+        let brush_index = vec![0; m * n];
+        Self::try_with_brush_map(
+            shape,
+            vec![void_brush],
+            vec![solid_brush],
+            brush_index,
+            active,
+            policy,
+        )
+    }
+
+    /// Like `Design::with_brushes`, with a brush per touch: the touches at
+    /// pixel `k` use `void_brushes[brush_index[k]]` and
+    /// `solid_brushes[brush_index[k]]`, e.g. for tighter rules near ports than
+    /// in the bulk.
+    ///
+    /// Panics where `Design::try_with_brush_map` returns an error.
+    pub fn with_brush_map(
+        shape: (usize, usize),
+        void_brushes: Vec<Brush>,
+        solid_brushes: Vec<Brush>,
+        brush_index: Vec<usize>,
+        active: Vec<bool>,
+        policy: InactivePolicy,
+    ) -> Self {
+        let result = Self::try_with_brush_map(
+            shape,
+            void_brushes,
+            solid_brushes,
+            brush_index,
+            active,
+            policy,
+        );
+        match result {
+            Ok((design, _)) => design,
+            Err(error) => panic!("{error}"),
+        }
+    }
+
+    /// Like `Design::with_brush_map`, but returns an error unless there are as
+    /// many (and at least one) void as solid brushes and `brush_index` holds a
+    /// valid index for every pixel, or for brushes the generator cannot use
+    /// (see `BrushIssue::is_fatal`). The other issues of the brushes (see
+    /// `Brush::analyze`), which the generated designs may not respect, are
+    /// returned along with the design.
+    pub fn try_with_brush_map(
+        shape: (usize, usize),
+        void_brushes: Vec<Brush>,
+        solid_brushes: Vec<Brush>,
+        brush_index: Vec<usize>,
+        active: Vec<bool>,
+        policy: InactivePolicy,
+    ) -> Result<(Self, Vec<BrushIssue>), GeneratorError> {
+        let (m, n) = shape;
+        if void_brushes.is_empty() {
+            return Err(GeneratorError::NoBrushes);
+        }
+        if void_brushes.len() != solid_brushes.len() {
+            return Err(GeneratorError::BrushCountMismatch {
+                void: void_brushes.len(),
+                solid: solid_brushes.len(),
+            });
+        }
+        if brush_index.len() != m * n {
+            return Err(GeneratorError::BrushMapMismatch {
+                expected: m * n,
+                actual: brush_index.len(),
+            });
+        }
+        if let Some(k) = (0..m * n).find(|k| brush_index[*k] >= void_brushes.len()) {
+            return Err(GeneratorError::InvalidBrushIndex {
+                pos: (k / n, k % n),
+                index: brush_index[k],
+                brushes: void_brushes.len(),
+            });
+        }
+        let mut issues = Vec::new();
+        for brush in void_brushes.iter().chain(solid_brushes.iter()) {
+            let brush_issues = brush.check().map_err(GeneratorError::InvalidBrush)?;
+            for issue in brush_issues.into_iter() {
+                if !issues.contains(&issue) {
+                    issues.push(issue);
+                }
+            }
+        }
+        let stencils = |brushes: &Vec<Brush>, opposite: &Vec<Brush>| -> (Vec<Stencils>, Brush) {
+            let mut very_big_brushes = Vec::new();
+            let stencils = brushes
+                .iter()
+                .map(|brush| {
+                    let big_brushes: Vec<Brush> = opposite
+                        .iter()
+                        .map(|opposite| compute_big_brush_pair(brush, opposite))
+                        .collect();
+                    let very_big_brush = compute_very_big_brush_set(brush, brushes, opposite);
+                    let stencils = Stencils::new(brush, &big_brushes, &very_big_brush, shape);
+                    very_big_brushes.push(very_big_brush);
+                    stencils
+                })
+                .collect();
+            (stencils, Brush::union(&very_big_brushes))
+        };
+        let (void_stencils, very_big_brush) = stencils(&void_brushes, &solid_brushes);
+        let (solid_stencils, _) = stencils(&solid_brushes, &void_brushes);
+        let big_brush = Brush::union(
+            &void_brushes
+                .iter()
+                .flat_map(|v| solid_brushes.iter().map(|s| compute_big_brush_pair(v, s)))
+                .collect::<Vec<_>>(),
+        );
+        let design = Self::with_stencils(
+            (void_brushes, solid_brushes),
             big_brush,
             very_big_brush,
+            (void_stencils, solid_stencils),
+            brush_index,
+            active,
+            policy,
+        );
+        Ok((design, issues))
+    }
 
-            void: new_array(size_x * size_y, false),
-            solid: new_array(size_x * size_y, false),
+    /// A design on the grid of the stencils, which need not be planar.
+    ///
+    /// `brushes` and `stencils` hold the void and the solid side, per brush
+    /// (see `Design::with_brush_map`). The brushes are kept for reference only;
+    /// all stamping goes through the stencils. `brush_index` and `active` are
+    /// indexed like the grid (see `Grid`).
+    pub fn with_stencils(
+        brushes: (Vec<Brush>, Vec<Brush>),
+        big_brush: Brush,
+        very_big_brush: Brush,
+        stencils: (Vec<Stencils>, Vec<Stencils>),
+        brush_index: Vec<usize>,
+        active: Vec<bool>,
+        policy: InactivePolicy,
+    ) -> Self {
+        let (brushes, solid_brushes) = brushes;
+        let (stencils, solid_stencils) = stencils;
+        let grid = stencils[0].brush.grid.clone();
+        let (size_x, size_y) = grid.rows();
+        let coverage = |stencils: &Vec<Stencils>| -> Vec<u32> {
+            (0..size_x * size_y)
+                .map(|k| touch_count(stencils, &brush_index, (k / size_y, k % size_y)))
+                .collect()
+        };
 
-            void_pixel_impossible: new_array(size_x * size_y, false),
-            void_pixel_existing: new_array(size_x * size_y, false),
-            void_pixel_required: new_array(size_x * size_y, false),
+        let mut design = Self {
+            shape: (size_x, size_y),
+            grid,
+            brushes,
+            solid_brushes,
+            big_brush,
+            very_big_brush,
+            void_touch_coverage: coverage(&stencils),
+            solid_touch_coverage: coverage(&solid_stencils),
+            brush_index,
+            stencils,
+            solid_stencils,
+
+            layers: Layers::new(size_x * size_y),
+            provenance: Provenance::new(size_x * size_y),
 
-            solid_pixel_impossible: new_array(size_x * size_y, false),
-            solid_pixel_existing: new_array(size_x * size_y, false),
-            solid_pixel_required: new_array(size_x * size_y, false),
+            any_inactive: active.iter().any(|a| !a),
+            active,
+            inactive_policy: policy,
 
-            void_touch_required: new_array(size_x * size_y, false),
-            void_touch_invalid: new_array(size_x * size_y, false),
-            void_touch_existing: new_array(size_x * size_y, false),
+            void_pixel_required_pending: Vec::new(),
+            solid_pixel_required_pending: Vec::new(),
 
-            solid_touch_required: new_array(size_x * size_y, false),
-            solid_touch_invalid: new_array(size_x * size_y, false),
-            solid_touch_existing: new_array(size_x * size_y, false),
+            journal: None,
         };
+        design.deactivate_inactive_pixels();
+        design
     }
 
-    pub fn add_void_touch(
-        &mut self,
-        pos: (usize, usize),
-    ) -> (Vec<(usize, usize)>, Vec<(usize, usize)>) {
+    fn deactivate_inactive_pixels(&mut self) {
+        if !self.any_inactive {
+            return;
+        }
+        let (m, n) = self.shape;
+        let policy = self.inactive_policy;
+        for k in 0..m * n {
+            let (void_invalid, solid_invalid) = self.outside_active_region(k);
+            if void_invalid {
+                self.forbid_void_touch_silently(k);
+            }
+            if solid_invalid {
+                self.invert();
+                self.forbid_void_touch_silently(k);
+                self.invert();
+            }
+        }
+        for k in (0..m * n).filter(|k| !self.active[*k]) {
+            let (void_existing, solid_existing) = match policy {
+                InactivePolicy::Solid => (false, true),
+                InactivePolicy::Void => (true, false),
+                InactivePolicy::Forbidden => (false, false),
+            };
+            self.layers[Layer::VoidPixelExisting][k] = void_existing;
+            self.layers[Layer::VoidPixelImpossible][k] = !void_existing;
+            self.layers[Layer::SolidPixelExisting][k] = solid_existing;
+            self.layers[Layer::SolidPixelImpossible][k] = !solid_existing;
+        }
+    }
+
+    /// Whether the active region rules out the void and the solid touch at
+    /// pixel `k` (see `Design::with_active_region`).
+    fn outside_active_region(&self, k: usize) -> (bool, bool) {
+        if !self.active[k] {
+            return (true, true);
+        }
+        let (_, n) = self.shape;
+        let (pos, b) = ((k / n, k % n), self.brush_index[k]);
+        let void_outside = !self.stencils[b].brush.all(pos, |p| self.active[p]);
+        let solid_outside = !self.solid_stencils[b].brush.all(pos, |p| self.active[p]);
+        match self.inactive_policy {
+            InactivePolicy::Solid => (void_outside, false),
+            InactivePolicy::Void => (false, solid_outside),
+            InactivePolicy::Forbidden => (void_outside, solid_outside),
+        }
+    }
+
+    /// Invalidate a void touch without flagging the pixels it leaves uncovered.
+    fn forbid_void_touch_silently(&mut self, touch: usize) {
+        let mut uncovered_pixels = Vec::new();
+        invalidate_touch(
+            &self.stencils[self.brush_index[touch]].brush,
+            &mut self.layers[Layer::VoidTouchInvalid],
+            &mut self.void_touch_coverage,
+            touch,
+            &mut uncovered_pixels,
+        );
+    }
+
+    pub fn add_void_touch(&mut self, pos: (usize, usize)) -> TouchEffects {
+        self.add_void_touch_of_kind(pos, TouchKind::Primary)
+    }
+
+    /// Like `add_void_touch`, tagging the pixels it assigns with `kind`.
+    pub fn add_void_touch_of_kind(&mut self, pos: (usize, usize), kind: TouchKind) -> TouchEffects {
         let profiler = Profiler::start("add_void_touch");
 
-        self.void_brush_at_pos(pos);
+        self.save_footprint(pos);
+        self.void_brush_at_pos(pos, Some(kind));
         self.void_touch_at_pos(pos);
-        self.big_void_brush_at_pos(pos);
-        let required_pixels = self.find_required_pixels_around_pos(pos);
+        let uncovered_pixels = self.big_void_brush_at_pos(pos);
+        let required_pixels = self.find_required_pixels_around_pos(pos, uncovered_pixels);
         self.take_free_void_touches_around_pos(pos);
         let resolving_touches = self.find_resolving_touches_for_required_pixels(&required_pixels);
+        self.provenance.iterations += 1;
         profiler.stop();
         return (required_pixels, resolving_touches);
     }
 
-    pub fn add_solid_touch(
-        &mut self,
-        pos: (usize, usize),
-    ) -> (Vec<(usize, usize)>, Vec<(usize, usize)>) {
+    pub fn add_solid_touch(&mut self, pos: (usize, usize)) -> TouchEffects {
         self.invert();
         let (required_pixels, resolving_touches) = self.add_void_touch(pos);
         self.invert();
         return (required_pixels, resolving_touches);
     }
 
-    fn big_void_brush_at_pos(&mut self, pos: (usize, usize)) {
-        apply_brush(
-            self.shape,
-            &mut self.solid_touch_invalid,
-            &self.big_brush,
-            pos,
-            true,
-        )
+    /// Stamp a void touch without searching for required pixels or free touches.
+    pub fn stamp_void_touch(&mut self, pos: (usize, usize)) {
+        self.void_brush_at_pos(pos, None);
+        self.void_touch_at_pos(pos);
+        self.big_void_brush_at_pos(pos);
     }
 
-    fn void_brush_at_pos(&mut self, pos: (usize, usize)) {
-        multi_apply_brush(
-            self.shape,
-            &mut vec![
-                &mut self.void,
-                &mut self.void_pixel_impossible,
-                &mut self.void_pixel_existing,
-                &mut self.void_pixel_required,
-                &mut self.solid_pixel_impossible,
-                &mut self.solid_pixel_existing,
-                &mut self.solid_pixel_required,
-            ],
-            &self.brush,
-            pos,
-            &vec![true, false, true, false, true, false, false],
+    pub fn stamp_solid_touch(&mut self, pos: (usize, usize)) {
+        self.invert();
+        self.stamp_void_touch(pos);
+        self.invert();
+    }
+
+    /// Invalidate the solid touches overlapping a void touch at `pos`.
+    ///
+    /// Returns the pixels that lost their last valid covering solid touch.
+    fn big_void_brush_at_pos(&mut self, pos: (usize, usize)) -> Vec<(usize, usize)> {
+        let mut uncovered_pixels = Vec::new();
+        let (_, n) = self.shape;
+        let brush_index = &self.brush_index;
+        let solid_touch_invalid = &mut self.layers[Layer::SolidTouchInvalid];
+        let solid_touch_coverage = &mut self.solid_touch_coverage;
+        let big_brushes = &self.stencils[brush_index[pos.0 * n + pos.1]].big_brushes;
+        // the solid touches of each brush overlapping this touch
+        for (b, big_brush) in big_brushes.iter().enumerate() {
+            big_brush.for_each(pos, |touch| {
+                if brush_index[touch] != b {
+                    return;
+                }
+                invalidate_touch(
+                    &self.solid_stencils[b].brush,
+                    solid_touch_invalid,
+                    solid_touch_coverage,
+                    touch,
+                    &mut uncovered_pixels,
+                );
+            });
+        }
+        uncovered_pixels
+    }
+
+    /// Forbid a void touch that would otherwise be valid.
+    ///
+    /// Pixels that can no longer be covered by any void touch are flagged as
+    /// required solid pixels.
+    pub fn forbid_void_touch(&mut self, pos: (usize, usize)) {
+        let (_, n) = self.shape;
+        let mut uncovered_pixels = Vec::new();
+        invalidate_touch(
+            &self.stencils[self.brush_index[pos.0 * n + pos.1]].brush,
+            &mut self.layers[Layer::VoidTouchInvalid],
+            &mut self.void_touch_coverage,
+            pos.0 * n + pos.1,
+            &mut uncovered_pixels,
         );
+        for (i, j) in uncovered_pixels.into_iter() {
+            let k = i * n + j;
+            if !(self.layers[Layer::SolidPixelExisting][k]
+                | self.layers[Layer::SolidPixelImpossible][k])
+            {
+                self.layers[Layer::SolidPixelRequired][k] = true;
+                self.solid_pixel_required_pending.push((i, j));
+            }
+        }
+    }
+
+    /// Remove existing void touches, e.g. to regenerate part of a design.
+    ///
+    /// The pixels no other void touch covers become unassigned, and the solid
+    /// touches they overlapped become valid again, unless another void touch
+    /// or the active region still rules them out. Required pixels are not
+    /// flagged (see `Design::find_required_pixels`).
+    pub fn remove_void_touches(&mut self, touches: &[(usize, usize)]) {
+        let (_, n) = self.shape;
+        let touches: Vec<usize> = touches
+            .iter()
+            .map(|(i, j)| i * n + j)
+            .filter(|t| self.layers[Layer::VoidTouchExisting][*t])
+            .collect();
+        // the pixels and the solid touches that may change, each once
+        let (mut pixels, mut solid_touches) = (Vec::new(), Vec::new());
+        let mut seen = vec![(false, false); self.grid.size()];
+        for t in touches.iter() {
+            self.layers[Layer::VoidTouchExisting][*t] = false;
+            let (pos, stencils) = ((t / n, t % n), &self.stencils[self.brush_index[*t]]);
+            stencils.brush.for_each(pos, |k| {
+                if !seen[k].0 {
+                    seen[k].0 = true;
+                    pixels.push(k);
+                }
+            });
+            for (b, big_brush) in stencils.big_brushes.iter().enumerate() {
+                big_brush.for_each(pos, |s| {
+                    if (self.brush_index[s] == b) & !seen[s].1 {
+                        seen[s].1 = true;
+                        solid_touches.push(s);
+                    }
+                });
+            }
+        }
+
+        for k in pixels.into_iter() {
+            if !self.active[k] | !self.layers[Layer::VoidPixelExisting][k] {
+                continue;
+            }
+            let existing = &self.layers[Layer::VoidTouchExisting];
+            let mut covered = false;
+            for_each_touch(&self.stencils, &self.brush_index, (k / n, k % n), |t| {
+                covered |= existing[t];
+            });
+            if covered {
+                continue;
+            }
+            self.layers.stamp_pixel(
+                k,
+                &[
+                    (Layer::Void, false),
+                    (Layer::VoidPixelExisting, false),
+                    (Layer::SolidPixelImpossible, false),
+                ],
+            );
+            self.provenance.origins[k] = None;
+        }
+
+        for s in solid_touches.into_iter() {
+            if !self.layers[Layer::SolidTouchInvalid][s] {
+                continue;
+            }
+            if self.any_inactive && self.outside_active_region(s).1 {
+                continue;
+            }
+            let (pos, stencils) = ((s / n, s % n), &self.solid_stencils[self.brush_index[s]]);
+            let existing = &self.layers[Layer::VoidTouchExisting];
+            let overlapped = stencils
+                .big_brushes
+                .iter()
+                .enumerate()
+                .any(|(b, big_brush)| {
+                    !big_brush.all(pos, |t| (self.brush_index[t] != b) | !existing[t])
+                });
+            if overlapped {
+                continue;
+            }
+            self.layers[Layer::SolidTouchInvalid][s] = false;
+            let solid_touch_coverage = &mut self.solid_touch_coverage;
+            stencils
+                .brush
+                .for_each(pos, |k| solid_touch_coverage[k] += 1);
+        }
+    }
+
+    pub fn remove_solid_touches(&mut self, touches: &[(usize, usize)]) {
+        self.invert();
+        self.remove_void_touches(touches);
+        self.invert();
+    }
+
+    fn void_brush_at_pos(&mut self, pos: (usize, usize), kind: Option<TouchKind>) {
+        let (_, n) = self.shape;
+        let brush = &self.stencils[self.brush_index[pos.0 * n + pos.1]].brush;
+        if let Some(kind) = kind {
+            let (active, existing) = (&self.active, &self.layers[Layer::VoidPixelExisting]);
+            let provenance = &mut self.provenance;
+            brush.for_each(pos, |k| {
+                if !active[k] | existing[k] {
+                    return;
+                }
+                provenance.record(k, kind);
+            });
+        }
+        self.layers.stamp_brush(brush, pos, &VOID_BRUSH_STAMP);
+        if self.any_inactive {
+            let active = &self.active;
+            let void = &mut self.layers[Layer::Void];
+            brush.for_each(pos, |k| void[k] &= active[k]);
+        }
+    }
+
+    /// The stencils of the void touch at `pos`.
+    pub fn stencils_at(&self, pos: (usize, usize)) -> &Stencils {
+        let (_, n) = self.shape;
+        &self.stencils[self.brush_index[pos.0 * n + pos.1]]
+    }
+
+    /// The brush of the void touches, the first one with a brush map (see
+    /// `Design::with_brush_map`). All brushes are in `brushes` and `solid_brushes`.
+    pub fn brush(&self) -> &Brush {
+        &self.brushes[0]
+    }
+
+    /// The stencils of the solid touch at `pos`.
+    pub fn solid_stencils_at(&self, pos: (usize, usize)) -> &Stencils {
+        let (_, n) = self.shape;
+        &self.solid_stencils[self.brush_index[pos.0 * n + pos.1]]
     }
 
     fn void_touch_at_pos(&mut self, pos: (usize, usize)) {
-        multi_apply_touch(
-            self.shape,
-            &mut vec![
-                &mut self.void_touch_required,
-                &mut self.void_touch_invalid,
-                &mut self.void_touch_existing,
-                &mut self.solid_touch_required,
-                &mut self.solid_touch_existing,
-            ],
-            pos,
-            &vec![false, false, true, false, false],
-        );
+        let (_, n) = self.shape;
+        self.layers
+            .stamp_pixel(pos.0 * n + pos.1, &VOID_TOUCH_STAMP);
     }
-    pub fn invert(&mut self) {
-        swap(&mut self.void, &mut self.solid);
-        swap(
-            &mut self.void_pixel_impossible,
-            &mut self.solid_pixel_impossible,
-        );
+
+    /// Start recording the state of the pixels that `Design::add_void_touch`
+    /// (or `add_solid_touch`) changes, so that `Design::rollback` can restore
+    /// the design as it is now; replaces the previous checkpoint.
+    ///
+    /// Unlike cloning the design, this costs only a box around every touch
+    /// (see `UnresolvedStrategy::Backtrack`).
+    pub fn checkpoint(&mut self) {
+        if self.journal.is_none() {
+            self.journal = Some(Journal {
+                footprint: self.touch_footprint(),
+                recording: false,
+                runs: Vec::new(),
+                layers: Vec::new(),
+                coverage: Vec::new(),
+                origins: Vec::new(),
+                void_pending: Vec::new(),
+                solid_pending: Vec::new(),
+                iterations: 0,
+            });
+        }
+        let journal = self.journal.as_mut().unwrap();
+        journal.runs.clear();
+        journal.layers.clear();
+        journal.coverage.clear();
+        journal.origins.clear();
+        journal.recording = true;
+        journal
+            .void_pending
+            .clone_from(&self.void_pixel_required_pending);
+        journal
+            .solid_pending
+            .clone_from(&self.solid_pixel_required_pending);
+        journal.iterations = self.provenance.iterations;
+    }
+
+    /// Restore the design to the last checkpoint and stop recording.
+    ///
+    /// The design must have the same orientation (see `Design::invert`) as at
+    /// the checkpoint, and only touches may have been added since.
+    pub fn rollback(&mut self) {
+        let Some(journal) = self.journal.as_mut() else {
+            return;
+        };
+        if !journal.recording {
+            return;
+        }
+        journal.recording = false;
+        // backwards, so that the pixels saved more than once get their first state
+        while let Some((start, len)) = journal.runs.pop() {
+            let run = start..start + len;
+            let at = journal.origins.len() - len;
+            self.provenance.origins[run.clone()].copy_from_slice(&journal.origins[at..]);
+            journal.origins.truncate(at);
+            let at = journal.coverage.len() - 2 * len;
+            self.void_touch_coverage[run.clone()].copy_from_slice(&journal.coverage[at..at + len]);
+            self.solid_touch_coverage[run.clone()].copy_from_slice(&journal.coverage[at + len..]);
+            journal.coverage.truncate(at);
+            let at = journal.layers.len() - Layer::ALL.len() * len;
+            journal.layers[at..]
+                .chunks(len)
+                .zip(Layer::ALL)
+                .for_each(|(saved, layer)| {
+                    self.layers[layer][run.clone()].copy_from_slice(saved);
+                });
+            journal.layers.truncate(at);
+        }
         swap(
-            &mut self.void_pixel_existing,
-            &mut self.solid_pixel_existing,
+            &mut self.void_pixel_required_pending,
+            &mut journal.void_pending,
         );
         swap(
-            &mut self.void_pixel_required,
-            &mut self.solid_pixel_required,
+            &mut self.solid_pixel_required_pending,
+            &mut journal.solid_pending,
         );
+        self.provenance.iterations = journal.iterations;
+    }
+
+    /// Stop recording and keep the changes since the last checkpoint.
+    pub fn release_checkpoint(&mut self) {
+        if let Some(journal) = self.journal.as_mut() {
+            journal.recording = false;
+        }
+    }
+
+    /// The box around the origin that a void touch at the origin may change
+    /// (see `Journal`), on both sides so that it holds after `invert`.
+    fn touch_footprint(&self) -> Stencil {
+        let ndim = self.grid.ndim();
+        let all = self.stencils.iter().chain(self.solid_stencils.iter());
+        let union = |stencils: Vec<&Stencil>| -> Vec<(i32, i32)> {
+            (0..ndim)
+                .map(|a| {
+                    let min = stencils.iter().map(|s| s.bounds[a].0).min().unwrap_or(0);
+                    let max = stencils.iter().map(|s| s.bounds[a].1).max().unwrap_or(0);
+                    (min.min(0), max.max(0))
+                })
+                .collect()
+        };
+        let brush = union(all.clone().map(|s| &s.brush).collect());
+        let big = union(all.clone().flat_map(|s| s.big_brushes.iter()).collect());
+        let very_big = union(all.map(|s| &s.very_big_brush).collect());
+        let mut offsets = vec![Vec::new()];
+        for a in 0..ndim {
+            let min = big[a].0.min(very_big[a].0) + brush[a].0;
+            let max = big[a].1.max(very_big[a].1) + brush[a].1;
+            offsets = offsets
+                .into_iter()
+                .flat_map(|o: Vec<i32>| {
+                    (min..=max).map(move |x| {
+                        let mut o = o.clone();
+                        o.push(x);
+                        o
+                    })
+                })
+                .collect();
+        }
+        Stencil::from_grid_offsets(&offsets, self.grid.clone())
+    }
+
+    /// Save the pixels a void touch at `pos` may change, while recording.
+    fn save_footprint(&mut self, pos: (usize, usize)) {
+        let Some(journal) = self.journal.as_mut() else {
+            return;
+        };
+        if !journal.recording {
+            return;
+        }
+        journal.footprint.for_each_run(pos, |start, len| {
+            let run = start..start + len;
+            journal.runs.push((start, len));
+            for layer in Layer::ALL {
+                journal
+                    .layers
+                    .extend_from_slice(&self.layers[layer][run.clone()]);
+            }
+            journal
+                .coverage
+                .extend_from_slice(&self.void_touch_coverage[run.clone()]);
+            journal
+                .coverage
+                .extend_from_slice(&self.solid_touch_coverage[run.clone()]);
+            journal
+                .origins
+                .extend_from_slice(&self.provenance.origins[run]);
+        });
+    }
+
+    pub fn invert(&mut self) {
+        self.layers.invert();
+        self.provenance.inverted = !self.provenance.inverted;
+        self.inactive_policy = self.inactive_policy.opposite();
         swap(
-            &mut self.void_touch_required,
-            &mut self.solid_touch_required,
+            &mut self.void_touch_coverage,
+            &mut self.solid_touch_coverage,
         );
-        swap(&mut self.void_touch_invalid, &mut self.solid_touch_invalid);
+        swap(&mut self.stencils, &mut self.solid_stencils);
         swap(
-            &mut self.void_touch_existing,
-            &mut self.solid_touch_existing,
+            &mut self.void_pixel_required_pending,
+            &mut self.solid_pixel_required_pending,
         );
     }
+
     fn take_free_void_touches_around_pos(&mut self, pos: (usize, usize)) {
+        let candidates = self.stencils_at(pos).very_big_brush.at(pos);
+        self.take_free_void_touches(candidates);
+    }
+
+    /// Take every free void touch in the design (e.g. after stamping seed touches).
+    pub fn take_all_free_void_touches(&mut self) {
+        let (m, n) = self.shape;
+        let candidates = (0..m * n)
+            .filter(|k| !self.layers[Layer::VoidTouchExisting][*k])
+            .map(|k| (k / n, k % n))
+            .collect();
+        self.take_free_void_touches(candidates);
+    }
+
+    fn take_free_void_touches(&mut self, candidates: Vec<(usize, usize)>) {
         let profiler1 = Profiler::start("find_free");
-        let free: Vec<(usize, usize)> = self
-            .very_big_brush
-            .at(pos, self.shape)
-            .into_iter()
-            .par_bridge()
+        let (_, n) = self.shape;
+        // forbidden (invalid) touches stay forbidden, even when free.
+        let free: Vec<(usize, usize)> = candidates
+            .into_par_iter()
+            .filter(|(i, j)| {
+                !(self.layers[Layer::VoidTouchInvalid][i * n + j]
+                    | self.layers[Layer::VoidTouchExisting][i * n + j])
+            })
             .filter(|pos| {
                 is_free_touch(
                     *pos,
-                    &self.brush,
-                    self.shape,
-                    &self.void_pixel_existing,
-                    &self.void_pixel_required,
+                    &self.stencils_at(*pos).brush,
+                    &self.layers[Layer::VoidPixelExisting],
+                    &self.layers[Layer::VoidPixelRequired],
                 )
             })
             .collect();
@@ -232,96 +898,151 @@ impl Design {
         let profiler2 = Profiler::start("take_free");
         for pos in free.into_iter() {
             self.void_touch_at_pos(pos);
-            self.void_brush_at_pos(pos);
+            self.void_brush_at_pos(pos, Some(TouchKind::Free));
         }
         profiler2.stop();
     }
-    fn find_required_pixels_around_pos(&mut self, pos: (usize, usize)) -> Vec<(usize, usize)> {
-        let profiler1 = Profiler::start("find_required");
 
-        let required_pixels: Vec<(usize, usize)> = self
-            .very_big_brush
-            .at(pos, self.shape)
+    /// Required pixels after a touch at `pos`: the pixels it left without valid
+    /// covering solid touch plus the pending required pixels around it.
+    fn find_required_pixels_around_pos(
+        &mut self,
+        pos: (usize, usize),
+        uncovered_pixels: Vec<(usize, usize)>,
+    ) -> Vec<(usize, usize)> {
+        let profiler = Profiler::start("find_required");
+        let (_, n) = self.shape;
+        let very_big_brush = &self.stencils[self.brush_index[pos.0 * n + pos.1]].very_big_brush;
+
+        let void_pixel_required = &self.layers[Layer::VoidPixelRequired];
+        self.void_pixel_required_pending
+            .retain(|(ip, jp)| void_pixel_required[ip * n + jp]);
+        let pending_pixels: Vec<(usize, usize)> = self
+            .void_pixel_required_pending
+            .iter()
+            .filter(|pending| very_big_brush.box_contains(pos, **pending))
+            .copied()
+            .collect();
+        profiler.stop();
+
+        let mut required_pixels = self.find_required_pixels(uncovered_pixels);
+        required_pixels.extend(pending_pixels);
+        required_pixels
+    }
+
+    /// Flag (and return) the void pixels among `positions` that can no longer become solid.
+    pub fn find_required_pixels(&mut self, positions: Vec<(usize, usize)>) -> Vec<(usize, usize)> {
+        let profiler = Profiler::start("flag_required");
+        let (_, n) = self.shape;
+        let required_pixels: Vec<(usize, usize)> = positions
             .into_iter()
-            .par_bridge()
-            .filter(|pos| {
-                is_required_pixel(
-                    *pos,
-                    self.shape,
-                    &self.brush,
-                    &self.void_pixel_existing,
-                    &self.void_pixel_impossible,
-                    &self.solid_touch_invalid,
-                )
+            .filter(|(i, j)| {
+                let k = i * n + j;
+                !(self.layers[Layer::VoidPixelExisting][k]
+                    | self.layers[Layer::VoidPixelImpossible][k])
+                    & !self.layers[Layer::VoidPixelRequired][k]
+                    & (self.solid_touch_coverage[k] == 0)
             })
             .collect();
-        profiler1.stop();
-
-        let profiler2 = Profiler::start("flag_required");
-        for (ip, jp) in required_pixels.iter() {
-            multi_apply_touch(
-                self.shape,
-                &mut vec![&mut self.void_pixel_required],
-                (*ip, *jp),
-                &vec![true],
-            );
+        for (i, j) in required_pixels.iter() {
+            self.layers[Layer::VoidPixelRequired][i * n + j] = true;
         }
-        profiler2.stop();
-
+        self.void_pixel_required_pending
+            .extend(required_pixels.iter());
+        profiler.stop();
         return required_pixels;
     }
 
-    fn find_resolving_touches_for_required_pixels(
+    pub fn find_resolving_touches_for_required_pixels(
         &mut self,
         required_pixels: &Vec<(usize, usize)>,
     ) -> Vec<(usize, usize)> {
         let (_, n) = self.shape;
-        let resolving_touches: Vec<(usize, usize)> = required_pixels
-            .into_iter()
-            .filter(|(i, j)| !self.void_pixel_existing[i * n + j])
-            .map(|(i, j)| {
-                self.brush
-                    .at((*i, *j), self.shape)
-                    .into_iter()
-                    .filter(|(i, j)| !self.void_touch_invalid[*i * n + j])
-            })
-            .flatten()
-            .collect();
+        let mut resolving_touches: Vec<(usize, usize)> = Vec::new();
+        for (i, j) in required_pixels.iter() {
+            if self.layers[Layer::VoidPixelExisting][i * n + j] {
+                continue;
+            }
+            for_each_touch(&self.stencils, &self.brush_index, (*i, *j), |k| {
+                if !self.layers[Layer::VoidTouchInvalid][k] {
+                    resolving_touches.push((k / n, k % n));
+                }
+            });
+        }
         return resolving_touches;
     }
 }
 
-fn is_required_pixel(
+impl Index<Layer> for Design {
+    type Output = Vec<bool>;
+    fn index(&self, layer: Layer) -> &Vec<bool> {
+        &self.layers[layer]
+    }
+}
+
+impl IndexMut<Layer> for Design {
+    fn index_mut(&mut self, layer: Layer) -> &mut Vec<bool> {
+        &mut self.layers[layer]
+    }
+}
+
+/// Call `f` with every touch (of any brush in `stencils`) that covers pixel `pos`.
+pub fn for_each_touch<F: FnMut(usize)>(
+    stencils: &Vec<Stencils>,
+    brush_index: &[usize],
     pos: (usize, usize),
-    shape: (usize, usize),
-    brush: &Brush,
-    void_pixel_existing: &Vec<bool>,
-    void_pixel_impossible: &Vec<bool>,
-    solid_touch_invalid: &Vec<bool>,
-) -> bool {
-    let (ip, jp) = pos;
-    let (_, n) = shape;
-    if void_pixel_existing[ip * n + jp] | void_pixel_impossible[ip * n + jp] {
-        return false;
-    }
-    let all_touches_invalid = brush
-        .at((ip, jp), shape)
-        .iter()
-        .all(|(i, j)| solid_touch_invalid[i * n + j]);
-    return all_touches_invalid;
+    mut f: F,
+) {
+    if let [stencils] = stencils.as_slice() {
+        stencils.reflected_brush.for_each(pos, f);
+        return;
+    }
+    for (b, stencils) in stencils.iter().enumerate() {
+        stencils.reflected_brush.for_each(pos, |t| {
+            if brush_index[t] == b {
+                f(t);
+            }
+        });
+    }
+}
+
+/// The number of touches (of any brush in `stencils`) that cover pixel `pos`.
+pub fn touch_count(stencils: &Vec<Stencils>, brush_index: &[usize], pos: (usize, usize)) -> u32 {
+    if let [stencils] = stencils.as_slice() {
+        return stencils.reflected_brush.count(pos) as u32;
+    }
+    let mut count = 0;
+    for_each_touch(stencils, brush_index, pos, |_| count += 1);
+    count
+}
+
+fn invalidate_touch(
+    brush: &Stencil,
+    touch_invalid: &mut [bool],
+    touch_coverage: &mut [u32],
+    touch: usize,
+    uncovered_pixels: &mut Vec<(usize, usize)>,
+) {
+    if touch_invalid[touch] {
+        return;
+    }
+    touch_invalid[touch] = true;
+    let (_, n) = brush.shape;
+    // the pixels covered by this touch
+    brush.for_each((touch / n, touch % n), |k| {
+        touch_coverage[k] -= 1;
+        if touch_coverage[k] == 0 {
+            uncovered_pixels.push((k / n, k % n));
+        }
+    });
 }
 
 fn is_free_touch(
     pos: (usize, usize),
-    brush: &Brush,
-    shape: (usize, usize),
+    brush: &Stencil,
     void_pixel_existing: &Vec<bool>,
     void_pixel_required: &Vec<bool>,
 ) -> bool {
-    let (_, n) = shape;
-    let is_free_touch = brush
-        .at(pos, shape)
-        .iter()
-        .all(|(i_, j_)| void_pixel_existing[i_ * n + j_] | void_pixel_required[i_ * n + j_]);
+    let is_free_touch = brush.all(pos, |k| void_pixel_existing[k] | void_pixel_required[k]);
     return is_free_touch;
 }
@@ -0,0 +1,196 @@
+use super::brushes::{Brush, Rasterization};
+use super::generator::{generate_feasible_design_with_brushes, GeneratorOptions};
+use super::layers::Layer;
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+pub fn test_design_rules() {
+    let rules = DesignRules::new(120.0, 100.0, 10.0);
+    let enforced = rules.enforced().unwrap();
+    println!("{enforced}");
+    let (void_brush, solid_brush) = (rules.void_brush().unwrap(), rules.solid_brush().unwrap());
+    void_brush.visualize();
+    solid_brush.visualize();
+
+    let (m, n) = (60, 60);
+    let latent_t: Vec<f32> = (0..m * n)
+        .map(|k| ((k / n) as f32 / 5.0).sin() + ((k % n) as f32 / 7.0).cos())
+        .collect();
+    let (design, _) = generate_feasible_design_with_brushes(
+        (m, n),
+        &latent_t,
+        void_brush,
+        solid_brush,
+        &GeneratorOptions::default(),
+    )
+    .unwrap();
+    design.visualize();
+    println!(
+        "feasible: {}, invariant violations: {}, fill factor: {:.2}",
+        design.is_feasible(),
+        design.check_invariants().len(),
+        design[Layer::Solid].iter().filter(|s| **s).count() as f32 / (m * n) as f32,
+    );
+
+    let rules = DesignRules {
+        shape: BrushShape::NotchedSquare,
+        ..rules
+    };
+    println!("{}", rules.enforced().unwrap());
+}
+
+/// The family of brushes built for a design rule.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BrushShape {
+    /// a disk (`Brush::ellipse` with equal axes).
+    Circle,
+    /// a square with notches of a fifth of its width (`Brush::notched_square`).
+    NotchedSquare,
+}
+
+impl BrushShape {
+    /// The brush of this shape that is `diameter` pixels wide.
+    pub fn brush(&self, diameter: usize) -> Brush {
+        let d = diameter as f32;
+        match self {
+            Self::Circle => Brush::ellipse((d, d), 0.0, Rasterization::Center),
+            Self::NotchedSquare => Brush::notched_square(diameter, diameter / 5),
+        }
+    }
+}
+
+impl FromStr for BrushShape {
+    type Err = DesignRuleError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "circle" => Ok(Self::Circle),
+            "notched-square" | "notched_square" => Ok(Self::NotchedSquare),
+            _ => Err(DesignRuleError::UnknownShape(s.to_string())),
+        }
+    }
+}
+
+/// Minimum feature sizes in physical units (e.g. nanometres), for a grid with
+/// a `pitch` of the same units per pixel.
+///
+/// Solid features are unions of solid brushes and void features are unions of
+/// void brushes, so the solid brush enforces `min_width` and the void brush
+/// enforces `min_gap`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DesignRules {
+    pub min_width: f32,
+    pub min_gap: f32,
+    pub pitch: f32,
+    pub shape: BrushShape,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DesignRuleError {
+    /// a rule (or the pitch) that is not a positive length.
+    InvalidLength {
+        rule: &'static str,
+        value: f32,
+    },
+    UnknownShape(String),
+}
+
+impl fmt::Display for DesignRuleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::InvalidLength { rule, value } => {
+                write!(f, "the {rule} should be positive, got {value}")
+            }
+            Self::UnknownShape(shape) => write!(
+                f,
+                "unknown brush shape '{shape}' (expected 'circle' or 'notched-square')"
+            ),
+        }
+    }
+}
+
+impl Error for DesignRuleError {}
+
+/// The dimensions enforced by the brushes of a `DesignRules`, in the units of
+/// the rules. Brushes are whole pixels wide, so these are at least the rules.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EnforcedDimensions {
+    pub min_width: f32,
+    pub min_gap: f32,
+    pub solid_brush_shape: (usize, usize),
+    pub void_brush_shape: (usize, usize),
+}
+
+impl fmt::Display for EnforcedDimensions {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (sm, sn) = self.solid_brush_shape;
+        let (vm, vn) = self.void_brush_shape;
+        write!(
+            f,
+            "enforced min width: {} (solid brush: {sm}x{sn} pixels), enforced min gap: {} (void brush: {vm}x{vn} pixels)",
+            self.min_width, self.min_gap
+        )
+    }
+}
+
+impl DesignRules {
+    /// Rules enforced with circular brushes.
+    pub fn new(min_width: f32, min_gap: f32, pitch: f32) -> Self {
+        Self {
+            min_width,
+            min_gap,
+            pitch,
+            shape: BrushShape::Circle,
+        }
+    }
+
+    pub fn check(&self) -> Result<(), DesignRuleError> {
+        for (rule, value) in [
+            ("min width", self.min_width),
+            ("min gap", self.min_gap),
+            ("pitch", self.pitch),
+        ] {
+            if !(value.is_finite() & (value > 0.0)) {
+                return Err(DesignRuleError::InvalidLength { rule, value });
+            }
+        }
+        Ok(())
+    }
+
+    /// The brush of the solid touches, enforcing `min_width`.
+    pub fn solid_brush(&self) -> Result<Brush, DesignRuleError> {
+        self.check()?;
+        Ok(self.brush(self.min_width))
+    }
+
+    /// The brush of the void touches, enforcing `min_gap`.
+    pub fn void_brush(&self) -> Result<Brush, DesignRuleError> {
+        self.check()?;
+        Ok(self.brush(self.min_gap))
+    }
+
+    pub fn enforced(&self) -> Result<EnforcedDimensions, DesignRuleError> {
+        let (solid_brush, void_brush) = (self.solid_brush()?, self.void_brush()?);
+        Ok(EnforcedDimensions {
+            min_width: solid_brush.analyze().min_width * self.pitch,
+            min_gap: void_brush.analyze().min_width * self.pitch,
+            solid_brush_shape: solid_brush.shape,
+            void_brush_shape: void_brush.shape,
+        })
+    }
+
+    /// The smallest brush of `shape` that is at least `length` wide in every
+    /// direction (see `BrushProperties::min_width`).
+    fn brush(&self, length: f32) -> Brush {
+        // tolerate rounding errors, e.g. 120 nm on a 10 nm grid is 12 pixels.
+        let pixels = length / self.pitch * (1.0 - 1e-4);
+        let mut diameter = (pixels.ceil() as usize).max(1);
+        loop {
+            let brush = self.shape.brush(diameter);
+            if brush.analyze().min_width >= pixels {
+                return brush;
+            }
+            diameter += 1;
+        }
+    }
+}
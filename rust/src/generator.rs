@@ -1,7 +1,15 @@
 use super::array::read_f32;
-use super::brushes::Brush;
+use super::brushes::{Brush, BrushError, BrushIssue};
+use super::connectivity::ConnectivityRules;
 use super::debug::{counter, Profiler};
-use super::design::Design;
+use super::design::{Design, InactivePolicy, TouchEffects, TouchKind};
+use super::layers::Layer;
+use super::selection::{Candidate, Selection, SelectionStrategy, Side};
+use super::verification::{ConnectivityViolation, Violation};
+use super::warm_start::complete_seeded_design;
+use itertools::Itertools;
+use std::error::Error;
+use std::fmt;
 use std::mem::swap;
 // use super::visualize_f32_array;
 
@@ -13,79 +21,504 @@ pub fn test_generator() {
     let latent_t = read_f32(&format!("latent_t_{seed}_{m}x{n}.bin"));
     brush.visualize();
     // visualize_f32_array((m, n), &latent_t);
-    let design = generate_feasible_design((m, n), &latent_t, brush, false);
+    let design = generate_feasible_design((m, n), &latent_t, brush, false).unwrap();
     design.visualize();
     profiler.stop();
 }
 
+pub fn test_active_region() {
+    let seed = 42;
+    let (m, n) = (30, 30);
+    let brush = Brush::notched_square(5, 1);
+    let latent_t = read_f32(&format!("latent_t_{seed}_{m}x{n}.bin"));
+    // an L-shaped region with a hole for a fixed structure
+    let active: Vec<bool> = (0..m * n)
+        .map(|k| {
+            let (i, j) = (k / n, k % n);
+            let corner = (i < m / 2) & (j >= n / 2);
+            let hole = (18..24).contains(&i) & (6..12).contains(&j);
+            !(corner | hole)
+        })
+        .collect();
+    for policy in [
+        InactivePolicy::Solid,
+        InactivePolicy::Void,
+        InactivePolicy::Forbidden,
+    ] {
+        let options = GeneratorOptions {
+            unresolved_strategy: UnresolvedStrategy::Backtrack,
+            debug: true,
+            ..GeneratorOptions::default()
+        };
+        let (design, _) = generate_feasible_design_with_active_region(
+            (m, n),
+            &latent_t,
+            brush.clone(),
+            &active,
+            policy,
+            &options,
+        )
+        .unwrap();
+        println!("{policy:?}: feasible: {}", design.is_feasible());
+        design.visualize();
+    }
+}
+
+pub fn test_brush_issues() {
+    // the generator rejects brushes it cannot use and reports the other issues
+    let latent_t = vec![0.0; 10 * 10];
+    let options = GeneratorOptions::default();
+    let ring = vec![1.0, 1.0, 1.0, 1.0, 0.0, 1.0, 1.0, 1.0, 1.0];
+    let diagonal = vec![1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0];
+    for mask in [ring, diagonal] {
+        let brush = Brush::from_f32_mask((3, 3), &mask);
+        match generate_feasible_design_with_options((10, 10), &latent_t, brush, &options) {
+            Ok((_, report)) => println!("brush issues: {:?}", report.brush_issues),
+            Err(error) => println!("{error}"),
+        }
+    }
+}
+
+pub fn test_brush_map() {
+    let seed = 42;
+    let (m, n) = (30, 30);
+    let latent_t = read_f32(&format!("latent_t_{seed}_{m}x{n}.bin"));
+    // tighter rules near the ports along the left and right edges
+    let brushes = vec![Brush::notched_square(7, 1), Brush::notched_square(3, 0)];
+    let brush_index: Vec<usize> = (0..m * n)
+        .map(|k| ((k % n < 6) | (k % n >= n - 6)) as usize)
+        .collect();
+    let options = GeneratorOptions {
+        debug: true,
+        ..GeneratorOptions::default()
+    };
+    let (design, _) = generate_feasible_design_with_brush_map(
+        (m, n),
+        &latent_t,
+        brushes.clone(),
+        brushes.clone(),
+        &brush_index,
+        &options,
+    )
+    .unwrap();
+    println!("feasible: {}", design.is_feasible());
+    design.visualize();
+
+    // brush sets that do not match are rejected
+    for (void_brushes, solid_brushes) in [
+        (brushes.clone(), brushes[..1].to_vec()),
+        (Vec::new(), Vec::new()),
+    ] {
+        let result = generate_feasible_design_with_brush_map(
+            (m, n),
+            &latent_t,
+            void_brushes,
+            solid_brushes,
+            &brush_index,
+            &options,
+        );
+        if let Err(error) = result {
+            println!("{error}");
+        }
+    }
+}
+
+/// What to do when a touch leaves required pixels that no valid touch can resolve.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnresolvedStrategy {
+    /// keep going and only record where it happened (matches the python reference).
+    Report,
+    /// restore the design from before the offending touch and forbid that touch;
+    /// fails with `GeneratorError::UnresolvedPixels` if required pixels remain anyway.
+    Backtrack,
+}
+
+pub fn test_backtrack() {
+    let seed = 42;
+    let (m, n) = (300, 300);
+    let brush = Brush::notched_square(3, 0);
+    let latent_t = read_f32(&format!("latent_t_{seed}_{m}x{n}.bin"));
+    for strategy in [UnresolvedStrategy::Report, UnresolvedStrategy::Backtrack] {
+        let options = GeneratorOptions {
+            unresolved_strategy: strategy,
+            ..GeneratorOptions::default()
+        };
+        let profiler = Profiler::start(&format!("test_backtrack {strategy:?}"));
+        let result =
+            generate_feasible_design_with_options((m, n), &latent_t, brush.clone(), &options);
+        profiler.stop();
+        let (design, report) = match result {
+            Ok(result) => result,
+            Err(error) => {
+                println!("{strategy:?}: {error}");
+                continue;
+            }
+        };
+        for touch in report.unresolved_touches.iter() {
+            println!(
+                "{strategy:?}: {} touch {:?} left {} required pixels, backtracked: {}",
+                if touch.is_solid_touch {
+                    "solid"
+                } else {
+                    "void"
+                },
+                touch.pos,
+                touch.pixels.len(),
+                touch.backtracked
+            );
+        }
+        println!(
+            "{strategy:?}: unresolved pixels: {:?}, feasible: {}",
+            report.unresolved_pixels,
+            design.is_feasible()
+        );
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GeneratorOptions {
+    pub verbose: bool,
+    pub unresolved_strategy: UnresolvedStrategy,
+    pub selection: Selection,
+    /// check the design invariants after every step (slow).
+    pub debug: bool,
+    /// refuse (forbid) every primary touch that, with the touches it takes,
+    /// strands pixels that the rules require to be anchored (see
+    /// `Design::stranded_pixels`); the required pixels that forbidding leaves
+    /// are best handled with `UnresolvedStrategy::Backtrack`.
+    pub connectivity: Option<ConnectivityRules>,
+}
+
+impl Default for GeneratorOptions {
+    fn default() -> Self {
+        Self {
+            verbose: false,
+            unresolved_strategy: UnresolvedStrategy::Report,
+            selection: Selection::default(),
+            debug: false,
+            connectivity: None,
+        }
+    }
+}
+
+/// A (primary) touch after which some required pixels were left unresolved.
+#[derive(Debug, Clone)]
+pub struct UnresolvedTouch {
+    pub iteration: usize,
+    pub pos: (usize, usize),
+    pub is_solid_touch: bool,
+    pub pixels: Vec<(usize, usize)>,
+    pub backtracked: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum GeneratorError {
+    /// the latent array does not have one value per pixel.
+    ShapeMismatch { expected: usize, actual: usize },
+    /// NaN latent values have no place in the touch ordering.
+    NanInLatent { pos: (usize, usize) },
+    /// the active region mask does not have one value per pixel.
+    ActiveRegionMismatch { expected: usize, actual: usize },
+    /// no brushes were given.
+    NoBrushes,
+    /// the number of void brushes differs from the number of solid brushes.
+    BrushCountMismatch { void: usize, solid: usize },
+    /// the brush index map does not have one value per pixel.
+    BrushMapMismatch { expected: usize, actual: usize },
+    /// a brush index beyond the given brushes.
+    InvalidBrushIndex {
+        pos: (usize, usize),
+        index: usize,
+        brushes: usize,
+    },
+    /// a brush the generator cannot use (see `BrushIssue::is_fatal`).
+    InvalidBrush(BrushError),
+    /// a selection strategy with a temperature or length scale that is not positive.
+    InvalidSelection { selection: Selection },
+    /// (`UnresolvedStrategy::Backtrack`) required pixels are left in the final design.
+    UnresolvedPixels { pixels: Vec<(usize, usize)> },
+    /// (`complete_connected_design`) components break the connectivity rules
+    /// after the last repair round.
+    Disconnected {
+        violations: Vec<ConnectivityViolation>,
+    },
+    /// (debug mode) the design became inconsistent during a step.
+    InvariantViolated {
+        iteration: usize,
+        pos: (usize, usize),
+        violations: Vec<Violation>,
+    },
+}
+
+impl fmt::Display for GeneratorError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::ShapeMismatch { expected, actual } => write!(
+                f,
+                "expected a latent with {expected} values, got {actual} values."
+            ),
+            Self::NanInLatent { pos: (i, j) } => write!(f, "latent value at ({i}, {j}) is NaN."),
+            Self::ActiveRegionMismatch { expected, actual } => write!(
+                f,
+                "expected an active region with {expected} values, got {actual} values."
+            ),
+            Self::NoBrushes => write!(f, "expected at least one brush."),
+            Self::BrushCountMismatch { void, solid } => write!(
+                f,
+                "expected as many void brushes as solid brushes, got {void} and {solid}."
+            ),
+            Self::BrushMapMismatch { expected, actual } => write!(
+                f,
+                "expected a brush index map with {expected} values, got {actual} values."
+            ),
+            Self::InvalidBrushIndex {
+                pos: (i, j),
+                index,
+                brushes,
+            } => write!(
+                f,
+                "brush index {index} at ({i}, {j}) is out of range for {brushes} brushes."
+            ),
+            Self::InvalidBrush(error) => write!(f, "{error}"),
+            Self::InvalidSelection { selection } => write!(
+                f,
+                "invalid selection {selection:?}: the temperature or length scale must be positive."
+            ),
+            Self::UnresolvedPixels { pixels } => write!(
+                f,
+                "{} required pixels are unresolved after backtracking, e.g. {:?}.",
+                pixels.len(),
+                pixels[0]
+            ),
+            Self::Disconnected { violations } => write!(
+                f,
+                "{} connectivity violations are left after repairing, e.g. {}",
+                violations.len(),
+                violations[0]
+            ),
+            Self::InvariantViolated {
+                iteration,
+                pos: (i, j),
+                violations,
+            } => write!(
+                f,
+                "{} invariant violations after touch ({i}, {j}) in iteration {iteration}, e.g. {}",
+                violations.len(),
+                violations[0]
+            ),
+        }
+    }
+}
+
+impl Error for GeneratorError {}
+
+#[derive(Debug, Clone, Default)]
+pub struct GeneratorReport {
+    pub unresolved_touches: Vec<UnresolvedTouch>,
+    /// required pixels still unresolved in the final design.
+    pub unresolved_pixels: Vec<(usize, usize)>,
+    /// touches taken over from a previous design (when warm-starting).
+    pub reused_touches: usize,
+    /// issues of the brushes that the design may not respect (see `Brush::analyze`).
+    pub brush_issues: Vec<BrushIssue>,
+    /// primary touches refused because they would strand pixels (see
+    /// `GeneratorOptions::connectivity`).
+    pub refused_touches: usize,
+}
+
 pub fn generate_feasible_design(
     shape: (usize, usize),
-    latent_t: &Vec<f32>,
+    latent_t: &[f32],
     brush: Brush,
     verbose: bool,
-) -> Design {
+) -> Result<Design, GeneratorError> {
+    let options = GeneratorOptions {
+        verbose,
+        ..GeneratorOptions::default()
+    };
+    let (design, _) = generate_feasible_design_with_options(shape, latent_t, brush, &options)?;
+    Ok(design)
+}
+
+pub fn generate_feasible_design_with_options(
+    shape: (usize, usize),
+    latent_t: &[f32],
+    brush: Brush,
+    options: &GeneratorOptions,
+) -> Result<(Design, GeneratorReport), GeneratorError> {
+    let (m, n) = shape;
+    let active = vec![true; m * n];
+    let policy = InactivePolicy::Forbidden;
+    let (design, brush_issues) =
+        Design::try_with_brushes(shape, brush.clone(), brush, active, policy)?;
+    let (design, mut report) = complete_feasible_design(design, latent_t, options)?;
+    report.brush_issues = brush_issues;
+    Ok((design, report))
+}
+
+/// Generate a feasible design with separate brushes for the void and the solid
+/// touches (see `Design::with_brushes`).
+pub fn generate_feasible_design_with_brushes(
+    shape: (usize, usize),
+    latent_t: &[f32],
+    void_brush: Brush,
+    solid_brush: Brush,
+    options: &GeneratorOptions,
+) -> Result<(Design, GeneratorReport), GeneratorError> {
+    let (m, n) = shape;
+    let active = vec![true; m * n];
+    let policy = InactivePolicy::Forbidden;
+    let (design, brush_issues) =
+        Design::try_with_brushes(shape, void_brush, solid_brush, active, policy)?;
+    let (design, mut report) = complete_feasible_design(design, latent_t, options)?;
+    report.brush_issues = brush_issues;
+    Ok((design, report))
+}
+
+/// Generate a feasible design with a brush per touch (see `Design::with_brush_map`).
+pub fn generate_feasible_design_with_brush_map(
+    shape: (usize, usize),
+    latent_t: &[f32],
+    void_brushes: Vec<Brush>,
+    solid_brushes: Vec<Brush>,
+    brush_index: &[usize],
+    options: &GeneratorOptions,
+) -> Result<(Design, GeneratorReport), GeneratorError> {
+    let (m, n) = shape;
+    let active = vec![true; m * n];
+    let policy = InactivePolicy::Forbidden;
+    let (design, brush_issues) = Design::try_with_brush_map(
+        shape,
+        void_brushes,
+        solid_brushes,
+        brush_index.to_vec(),
+        active,
+        policy,
+    )?;
+    let (design, mut report) = complete_feasible_design(design, latent_t, options)?;
+    report.brush_issues = brush_issues;
+    Ok((design, report))
+}
+
+/// Generate a feasible design on the pixels marked in `active` only.
+///
+/// Inactive pixels are never assigned; `policy` decides which brushes may
+/// overlap them (see `Design::with_active_region`). Dead ends are more common
+/// along the region boundary, so `UnresolvedStrategy::Backtrack` is recommended.
+pub fn generate_feasible_design_with_active_region(
+    shape: (usize, usize),
+    latent_t: &[f32],
+    brush: Brush,
+    active: &[bool],
+    policy: InactivePolicy,
+    options: &GeneratorOptions,
+) -> Result<(Design, GeneratorReport), GeneratorError> {
+    let (m, n) = shape;
+    if active.len() != m * n {
+        return Err(GeneratorError::ActiveRegionMismatch {
+            expected: m * n,
+            actual: active.len(),
+        });
+    }
+    let (design, brush_issues) =
+        Design::try_with_brushes(shape, brush.clone(), brush, active.to_vec(), policy)?;
+    // pixels that no touch can reach any more are resolved before the first step
+    let (design, mut report) =
+        complete_seeded_design(design, &Vec::new(), &Vec::new(), latent_t, options)?;
+    report.brush_issues = brush_issues;
+    Ok((design, report))
+}
+
+/// Keep adding touches to a (possibly partially filled) design until every
+/// touch is either existing or invalid.
+pub fn complete_feasible_design(
+    design: Design,
+    latent_t: &[f32],
+    options: &GeneratorOptions,
+) -> Result<(Design, GeneratorReport), GeneratorError> {
+    let mut strategy = options.selection.strategy()?;
+    complete_feasible_design_with_strategy(design, latent_t, options, strategy.as_mut())
+}
+
+/// Like `complete_feasible_design`, but with a custom touch selection strategy.
+pub fn complete_feasible_design_with_strategy(
+    design: Design,
+    latent_t: &[f32],
+    options: &GeneratorOptions,
+    strategy: &mut dyn SelectionStrategy,
+) -> Result<(Design, GeneratorReport), GeneratorError> {
+    check_latent(design.shape, latent_t)?;
     let profiler = Profiler::start("generate_feasible_design");
+    let mut design = design;
+    let shape = design.shape;
     let (m, n) = shape;
+    let verbose = options.verbose;
+    let mut report = GeneratorReport::default();
 
     let mut void_latent_t: Vec<f32> = latent_t.iter().map(|l| -l).collect();
     let mut solid_latent_t: Vec<f32> = latent_t.iter().map(|l| *l).collect();
 
-    let mut design = Design::new(shape, brush);
-    let mut solid_indices: Vec<(usize, usize)> = (0..m * n).map(|k| (k / n, k % n)).collect();
+    // only the touches still open, so that completing a seeded design (see
+    // `complete_seeded_design`) costs in proportion to what is left to do.
+    let open_touches = |invalid: Layer, existing: Layer| -> Vec<(usize, usize)> {
+        (0..m * n)
+            .filter(|k| !(design[invalid][*k] | design[existing][*k]))
+            .map(|k| (k / n, k % n))
+            .collect()
+    };
+    let mut solid_indices = open_touches(Layer::SolidTouchInvalid, Layer::SolidTouchExisting);
     sort_indices_by_value(&mut solid_indices, &solid_latent_t, shape);
-    let mut void_indices: Vec<(usize, usize)> = (0..m * n).map(|k| (k / n, k % n)).collect();
+    let mut void_indices = open_touches(Layer::VoidTouchInvalid, Layer::VoidTouchExisting);
     sort_indices_by_value(&mut void_indices, &void_latent_t, shape);
+    // pixels stranded before the first touch are not held against any touch
+    let mut stranded = options
+        .connectivity
+        .as_ref()
+        .map_or(0, |rules| design.stranded_pixels(rules));
 
-    let mut prev_idxs = vec![(m, n), (m, n)];
-    let mut prev_indexer = 0;
     loop {
-        let ijv = void_indices.pop();
-        let ijs = solid_indices.pop();
+        discard_impossible_touches(
+            &mut void_indices,
+            &design[Layer::VoidTouchInvalid],
+            &design[Layer::VoidTouchExisting],
+            n,
+        );
+        discard_impossible_touches(
+            &mut solid_indices,
+            &design[Layer::SolidTouchInvalid],
+            &design[Layer::SolidTouchExisting],
+            n,
+        );
 
-        let ((iv, jv), (is, js)) = match (ijv, ijs) {
+        let side = match (void_indices.last(), solid_indices.last()) {
             (None, None) => break,
-            (Some(ijv), None) => (ijv, ijv),
-            (None, Some(ijs)) => (ijs, ijs),
-            (Some(ijv), Some(ijs)) => (ijv, ijs),
+            (Some(_), None) => Side::Void,
+            (None, Some(_)) => Side::Solid,
+            (Some(&(iv, jv)), Some(&(is, js))) => {
+                let void = Candidate {
+                    pos: (iv, jv),
+                    latent: void_latent_t[iv * n + jv],
+                };
+                let solid = Candidate {
+                    pos: (is, js),
+                    latent: solid_latent_t[is * n + js],
+                };
+                strategy.select(&void, &solid, &design, latent_t)
+            }
         };
 
-        let latent_v = void_latent_t[iv * n + jv];
-        let latent_s = solid_latent_t[is * n + js];
-
-        let is_solid_touch = latent_s > latent_v;
-
+        let is_solid_touch = side == Side::Solid;
         let (i, j) = if is_solid_touch {
-            void_indices.push((iv, jv));
-            (is, js)
+            solid_indices.pop().unwrap()
         } else {
-            solid_indices.push((is, js));
-            (iv, jv)
+            void_indices.pop().unwrap()
         };
 
-        // I thought I would not need a check like this, but I was wrong...
-        if (prev_idxs[0] == (i, j)) & (prev_idxs[1] == (i, j)) {
-            break;
-        } else {
-            prev_idxs[prev_indexer] = (i, j);
-            prev_indexer = (prev_indexer + 1) % 2
-        }
-
         if is_solid_touch {
             design.invert();
             swap(&mut solid_latent_t, &mut void_latent_t);
         }
 
-        let void_touch_possible =
-            !(design.void_touch_invalid[i * n + j] | design.void_touch_existing[i * n + j]);
-
-        if !void_touch_possible {
-            if is_solid_touch {
-                design.invert();
-                swap(&mut solid_latent_t, &mut void_latent_t);
-            }
-            continue;
-        }
-
         if verbose {
             println!("iteration {}", counter().value());
         }
@@ -98,9 +531,15 @@ pub fn generate_feasible_design(
             }
         }
 
-        let (mut required_pixels, mut resolving_touches) = void_step(&mut design, (i, j));
+        let backtrack = options.unresolved_strategy == UnresolvedStrategy::Backtrack;
+        if backtrack | options.connectivity.is_some() {
+            design.checkpoint();
+        }
 
-        resolve_required_void_pixels(
+        let (mut required_pixels, mut resolving_touches) =
+            void_step(&mut design, (i, j), TouchKind::Primary);
+
+        let unresolved_pixels = resolve_required_void_pixels(
             &mut design,
             &mut required_pixels,
             &mut resolving_touches,
@@ -109,12 +548,61 @@ pub fn generate_feasible_design(
             verbose,
         );
 
+        let backtracked = backtrack & !unresolved_pixels.is_empty();
+        if !unresolved_pixels.is_empty() {
+            if verbose {
+                println!("unresolved required pixels after touch ({i}, {j}).");
+            }
+            if backtracked {
+                design.rollback();
+                design.forbid_void_touch((i, j));
+            }
+            report.unresolved_touches.push(UnresolvedTouch {
+                iteration: counter().value(),
+                pos: (i, j),
+                is_solid_touch,
+                pixels: unresolved_pixels,
+                backtracked: backtrack,
+            });
+        }
+
+        if let (Some(rules), false) = (options.connectivity.as_ref(), backtracked) {
+            let rules = if is_solid_touch {
+                rules.opposite()
+            } else {
+                rules.clone()
+            };
+            let now = design.stranded_pixels(&rules);
+            if now > stranded {
+                if verbose {
+                    println!("touch ({i}, {j}) strands {} pixels.", now - stranded);
+                }
+                design.rollback();
+                design.forbid_void_touch((i, j));
+                report.refused_touches += 1;
+            } else {
+                stranded = now;
+            }
+        }
+
         // revert inversion
         if is_solid_touch {
             design.invert();
             swap(&mut solid_latent_t, &mut void_latent_t);
         }
 
+        if options.debug {
+            let violations = design.check_invariants();
+            if !violations.is_empty() {
+                profiler.stop();
+                return Err(GeneratorError::InvariantViolated {
+                    iteration: counter().value(),
+                    pos: (i, j),
+                    violations,
+                });
+            }
+        }
+
         //design.visualize();
 
         //if counter().gt(20) {
@@ -123,20 +611,27 @@ pub fn generate_feasible_design(
         counter().inc();
     }
 
+    design.release_checkpoint();
+    report.unresolved_pixels = design.unresolved_required_pixels();
+
     profiler.stop();
-    return design;
+    let backtrack = options.unresolved_strategy == UnresolvedStrategy::Backtrack;
+    if backtrack & !report.unresolved_pixels.is_empty() {
+        return Err(GeneratorError::UnresolvedPixels {
+            pixels: report.unresolved_pixels,
+        });
+    }
+    Ok((design, report))
 }
 
-pub fn void_step(
-    design: &mut Design,
-    pos: (usize, usize),
-) -> (Vec<(usize, usize)>, Vec<(usize, usize)>) {
+pub fn void_step(design: &mut Design, pos: (usize, usize), kind: TouchKind) -> TouchEffects {
     let (_, n) = design.shape;
     let (i, j) = pos;
-    if (design.void_touch_invalid[i * n + j]) | (design.void_touch_existing[i * n + j]) {
+    if (design[Layer::VoidTouchInvalid][i * n + j]) | (design[Layer::VoidTouchExisting][i * n + j])
+    {
         return (Vec::new(), Vec::new());
     }
-    let (required_pixels, resolving_touches) = design.add_void_touch(pos);
+    let (required_pixels, resolving_touches) = design.add_void_touch_of_kind(pos, kind);
     return (required_pixels, resolving_touches);
 }
 
@@ -144,31 +639,30 @@ pub fn resolve_required_void_pixels(
     design: &mut Design,
     required_pixels: &mut Vec<(usize, usize)>,
     resolving_touches: &mut Vec<(usize, usize)>,
-    void_latent_t: &Vec<f32>,
+    void_latent_t: &[f32],
     is_solid_touch: bool,
     verbose: bool,
-) {
+) -> Vec<(usize, usize)> {
     let profiler = Profiler::start("resolving");
     let (_, n) = design.shape;
+    let mut seen_required_pixels = required_pixels.clone();
     loop {
         sort_indices_by_value(resolving_touches, &void_latent_t, design.shape);
 
         let any_required_pixels = required_pixels
             .iter()
-            .any(|(i, j)| design.void_pixel_required[i * n + j]);
+            .any(|(i, j)| design[Layer::VoidPixelRequired][i * n + j]);
         if !any_required_pixels {
             break;
         }
         let (ir, jr) = match resolving_touches.pop() {
-            None => {
-                // println!("pop from resolving touches");
-                break;
-            }
+            None => break,
             Some(idxs) => idxs,
         };
 
         counter().inc();
-        let (mut new_required_pixels, mut new_resolving_touches) = void_step(design, (ir, jr));
+        let (mut new_required_pixels, mut new_resolving_touches) =
+            void_step(design, (ir, jr), TouchKind::Resolving);
         if verbose {
             println!("iteration {}", counter().value());
             if is_solid_touch {
@@ -178,19 +672,70 @@ pub fn resolve_required_void_pixels(
             }
         }
 
+        seen_required_pixels.extend(new_required_pixels.iter());
         swap(required_pixels, &mut new_required_pixels);
         swap(resolving_touches, &mut new_resolving_touches);
 
         sort_indices_by_value(resolving_touches, &void_latent_t, design.shape);
     }
+
+    // pixels that are still required either had no valid resolving touch left
+    // or were dropped from the chain above; both are unresolved for now.
+    let unresolved_pixels: Vec<(usize, usize)> = seen_required_pixels
+        .into_iter()
+        .filter(|(i, j)| design[Layer::VoidPixelRequired][i * n + j])
+        .unique()
+        .collect();
+
     profiler.stop();
+    unresolved_pixels
 }
 
-pub fn sort_indices_by_value(
+/// Pop touches that became invalid (or already exist) from the top of a sorted queue.
+fn discard_impossible_touches(
     indices: &mut Vec<(usize, usize)>,
-    values: &Vec<f32>,
+    touch_invalid: &[bool],
+    touch_existing: &[bool],
+    n: usize,
+) {
+    while let Some(&(i, j)) = indices.last() {
+        if !(touch_invalid[i * n + j] | touch_existing[i * n + j]) {
+            break;
+        }
+        indices.pop();
+    }
+}
+
+/// Validate a latent before it is used to order touches.
+pub fn check_latent(shape: (usize, usize), latent_t: &[f32]) -> Result<(), GeneratorError> {
+    let (m, n) = shape;
+    if latent_t.len() != m * n {
+        return Err(GeneratorError::ShapeMismatch {
+            expected: m * n,
+            actual: latent_t.len(),
+        });
+    }
+    match latent_t.iter().position(|l| l.is_nan()) {
+        Some(k) => Err(GeneratorError::NanInLatent {
+            pos: (k / n, k % n),
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Sort indices such that popping from the back yields them in priority order.
+///
+/// Priority is a total order: the largest value comes first and ties go to the
+/// smallest row-major index (like numpy's argmax in the python reference).
+/// Values are expected to be NaN-free (see `check_latent`).
+pub fn sort_indices_by_value(
+    indices: &mut [(usize, usize)],
+    values: &[f32],
     shape: (usize, usize),
 ) {
     let (_, n) = shape;
-    indices.sort_by(|(i, j), (k, l)| values[*i * n + j].partial_cmp(&values[*k * n + l]).unwrap());
+    indices.sort_unstable_by(|(i, j), (k, l)| {
+        let (a, b) = (*i * n + j, *k * n + l);
+        values[a].total_cmp(&values[b]).then(b.cmp(&a))
+    });
 }
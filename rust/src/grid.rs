@@ -0,0 +1,80 @@
+/// A row-major grid of any dimension.
+///
+/// Every grid is also seen as a planar grid of `(rows, columns)`, where a row
+/// flattens all axes but the last one. `Design` and the generator address
+/// pixels (or voxels) through this planar view, so the linear index of a
+/// position is the same in both views.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Grid {
+    pub dims: Vec<usize>,
+}
+
+impl Grid {
+    pub fn new(dims: Vec<usize>) -> Self {
+        assert!(!dims.is_empty(), "a grid needs at least one axis");
+        Self { dims }
+    }
+
+    pub fn plane(shape: (usize, usize)) -> Self {
+        Self::new(vec![shape.0, shape.1])
+    }
+
+    pub fn volume(shape: (usize, usize, usize)) -> Self {
+        Self::new(vec![shape.0, shape.1, shape.2])
+    }
+
+    pub fn ndim(&self) -> usize {
+        self.dims.len()
+    }
+
+    pub fn size(&self) -> usize {
+        self.dims.iter().product()
+    }
+
+    /// The planar `(rows, columns)` view of the grid.
+    pub fn rows(&self) -> (usize, usize) {
+        let n = *self.dims.last().unwrap();
+        if n == 0 {
+            return (0, 0);
+        }
+        (self.size() / n, n)
+    }
+
+    pub fn index(&self, pos: &[usize]) -> usize {
+        pos.iter()
+            .zip(self.dims.iter())
+            .fold(0, |k, (p, size)| k * size + p)
+    }
+
+    pub fn position(&self, k: usize) -> Vec<usize> {
+        let mut k = k;
+        let mut pos = vec![0; self.ndim()];
+        for (p, size) in pos.iter_mut().zip(self.dims.iter()).rev() {
+            *p = k % size;
+            k /= size;
+        }
+        pos
+    }
+
+    /// The linear offset of an offset in grid coordinates.
+    pub fn offset(&self, offset: &[i32]) -> isize {
+        offset
+            .iter()
+            .zip(self.dims.iter())
+            .fold(0, |k, (o, size)| k * (*size as isize) + (*o as isize))
+    }
+}
+
+/// All sums `a + b` of an offset of `a` and an offset of `b`, sorted and without duplicates.
+pub fn minkowski_sum(a: &[Vec<i32>], b: &[Vec<i32>]) -> Vec<Vec<i32>> {
+    let mut sum: Vec<Vec<i32>> = a
+        .iter()
+        .flat_map(|x| {
+            b.iter()
+                .map(move |y| x.iter().zip(y.iter()).map(|(x, y)| x + y).collect())
+        })
+        .collect();
+    sum.sort();
+    sum.dedup();
+    sum
+}
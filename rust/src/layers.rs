@@ -0,0 +1,144 @@
+use super::array::new_array;
+use super::stencil::Stencil;
+use std::ops::{Index, IndexMut};
+
+/// The boolean layers of a `Design`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Layer {
+    Void,
+    Solid,
+
+    VoidPixelImpossible,
+    VoidPixelExisting,
+    VoidPixelRequired,
+
+    SolidPixelImpossible,
+    SolidPixelExisting,
+    SolidPixelRequired,
+
+    VoidTouchRequired,
+    VoidTouchInvalid,
+    VoidTouchExisting,
+
+    SolidTouchRequired,
+    SolidTouchInvalid,
+    SolidTouchExisting,
+}
+
+pub const NUM_LAYERS: usize = 14;
+
+impl Layer {
+    pub const ALL: [Layer; NUM_LAYERS] = [
+        Layer::Void,
+        Layer::Solid,
+        Layer::VoidPixelImpossible,
+        Layer::VoidPixelExisting,
+        Layer::VoidPixelRequired,
+        Layer::SolidPixelImpossible,
+        Layer::SolidPixelExisting,
+        Layer::SolidPixelRequired,
+        Layer::VoidTouchRequired,
+        Layer::VoidTouchInvalid,
+        Layer::VoidTouchExisting,
+        Layer::SolidTouchRequired,
+        Layer::SolidTouchInvalid,
+        Layer::SolidTouchExisting,
+    ];
+
+    /// The same layer with void and solid swapped.
+    pub fn opposite(self) -> Layer {
+        match self {
+            Layer::Void => Layer::Solid,
+            Layer::Solid => Layer::Void,
+            Layer::VoidPixelImpossible => Layer::SolidPixelImpossible,
+            Layer::VoidPixelExisting => Layer::SolidPixelExisting,
+            Layer::VoidPixelRequired => Layer::SolidPixelRequired,
+            Layer::SolidPixelImpossible => Layer::VoidPixelImpossible,
+            Layer::SolidPixelExisting => Layer::VoidPixelExisting,
+            Layer::SolidPixelRequired => Layer::VoidPixelRequired,
+            Layer::VoidTouchRequired => Layer::SolidTouchRequired,
+            Layer::VoidTouchInvalid => Layer::SolidTouchInvalid,
+            Layer::VoidTouchExisting => Layer::SolidTouchExisting,
+            Layer::SolidTouchRequired => Layer::VoidTouchRequired,
+            Layer::SolidTouchInvalid => Layer::VoidTouchInvalid,
+            Layer::SolidTouchExisting => Layer::VoidTouchExisting,
+        }
+    }
+}
+
+/// Layers updated by stamping a void brush.
+pub const VOID_BRUSH_STAMP: [(Layer, bool); 7] = [
+    (Layer::Void, true),
+    (Layer::VoidPixelImpossible, false),
+    (Layer::VoidPixelExisting, true),
+    (Layer::VoidPixelRequired, false),
+    (Layer::SolidPixelImpossible, true),
+    (Layer::SolidPixelExisting, false),
+    (Layer::SolidPixelRequired, false),
+];
+
+/// Layers updated by placing a void touch.
+pub const VOID_TOUCH_STAMP: [(Layer, bool); 5] = [
+    (Layer::VoidTouchRequired, false),
+    (Layer::VoidTouchInvalid, false),
+    (Layer::VoidTouchExisting, true),
+    (Layer::SolidTouchRequired, false),
+    (Layer::SolidTouchExisting, false),
+];
+
+#[derive(Clone)]
+pub struct Layers {
+    layers: [Vec<bool>; NUM_LAYERS],
+}
+
+impl Layers {
+    pub fn new(size: usize) -> Self {
+        Self {
+            layers: std::array::from_fn(|_| new_array(size, false)),
+        }
+    }
+
+    /// Apply all `(layer, value)` pairs of `stamp` under `stencil` at `pos`.
+    pub fn stamp_brush(&mut self, stencil: &Stencil, pos: (usize, usize), stamp: &[(Layer, bool)]) {
+        stencil.for_each_run(pos, |start, len| {
+            for (layer, value) in stamp.iter() {
+                self.layers[*layer as usize][start..start + len].fill(*value);
+            }
+        });
+    }
+
+    /// Apply all `(layer, value)` pairs of `stamp` at the single index `k`.
+    pub fn stamp_pixel(&mut self, k: usize, stamp: &[(Layer, bool)]) {
+        for (layer, value) in stamp.iter() {
+            self.layers[*layer as usize][k] = *value;
+        }
+    }
+
+    /// Move a layer out, leaving an empty one in its place.
+    pub fn take(&mut self, layer: Layer) -> Vec<bool> {
+        std::mem::take(&mut self.layers[layer as usize])
+    }
+
+    /// Swap every void layer with its solid counterpart.
+    pub fn invert(&mut self) {
+        for layer in Layer::ALL.into_iter() {
+            let opposite = layer.opposite();
+            if (layer as usize) < (opposite as usize) {
+                self.layers.swap(layer as usize, opposite as usize);
+            }
+        }
+    }
+}
+
+impl Index<Layer> for Layers {
+    type Output = Vec<bool>;
+    fn index(&self, layer: Layer) -> &Vec<bool> {
+        &self.layers[layer as usize]
+    }
+}
+
+impl IndexMut<Layer> for Layers {
+    fn index_mut(&mut self, layer: Layer) -> &mut Vec<bool> {
+        &mut self.layers[layer as usize]
+    }
+}
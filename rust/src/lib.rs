@@ -1,8 +1,27 @@
 pub mod array;
+pub mod bench;
+pub mod brush_io;
 pub mod brushes;
+pub mod cli;
+pub mod connectivity;
 pub mod debug;
 pub mod design;
+pub mod design_rules;
 pub mod generator;
+pub mod grid;
+pub mod layers;
+pub mod metrics;
+pub mod multiscale;
+pub mod provenance;
 pub mod python;
+pub mod repair;
+pub mod selection;
+pub mod stack;
+pub mod state;
 pub mod status;
+pub mod stencil;
+pub mod tiling;
+pub mod verification;
 pub mod visualization;
+pub mod voxels;
+pub mod warm_start;
@@ -1,16 +1,63 @@
 use inverse_design_rs::visualization::test_visualization;
 
 // use inverse_design_rs::array::test_array;
+// use inverse_design_rs::bench::test_bench;
+// use inverse_design_rs::brush_io::test_brush_io;
 // use inverse_design_rs::brushes::test_brushes;
 // use inverse_design_rs::design::test_design;
+// use inverse_design_rs::design_rules::test_design_rules;
+use inverse_design_rs::cli::run;
+// use inverse_design_rs::connectivity::test_connectivity;
 use inverse_design_rs::debug::print_profiler_summary;
+// use inverse_design_rs::generator::test_active_region;
+// use inverse_design_rs::generator::test_backtrack;
+// use inverse_design_rs::generator::test_brush_issues;
+// use inverse_design_rs::generator::test_brush_map;
 use inverse_design_rs::generator::test_generator;
+// use inverse_design_rs::metrics::test_metrics;
+// use inverse_design_rs::multiscale::test_multiscale;
+// use inverse_design_rs::provenance::test_provenance;
+// use inverse_design_rs::repair::test_repair;
+// use inverse_design_rs::selection::test_selection;
+// use inverse_design_rs::stack::test_stack;
+// use inverse_design_rs::state::test_state;
+// use inverse_design_rs::tiling::test_tiling;
+// use inverse_design_rs::verification::test_verification;
+// use inverse_design_rs::voxels::test_voxels;
+// use inverse_design_rs::warm_start::test_warm_start;
 
 fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if !args.is_empty() {
+        if let Err(error) = run(&args) {
+            eprintln!("{error}");
+            std::process::exit(1);
+        }
+        return;
+    }
     test_visualization();
     //test_array();
     //test_brushes();
+    // test_brush_io();
+    // test_connectivity();
     // test_design();
+    // test_design_rules();
     test_generator();
+    // test_active_region();
+    // test_backtrack();
+    // test_brush_issues();
+    // test_brush_map();
+    // test_metrics();
+    // test_multiscale();
+    // test_provenance();
+    // test_repair();
+    // test_selection();
+    // test_stack();
+    // test_state();
+    // test_tiling();
+    // test_verification();
+    // test_voxels();
+    // test_warm_start();
+    // test_bench();
     print_profiler_summary();
 }
@@ -0,0 +1,294 @@
+use super::array::read_f32;
+use super::brushes::Brush;
+use super::design::{Design, TouchKind};
+use super::generator::generate_feasible_design;
+use super::layers::Layer;
+use super::repair::squared_distance_transform;
+
+pub fn test_metrics() {
+    let seed = 42;
+    let (m, n) = (30, 30);
+    let brush = Brush::notched_square(5, 1);
+    let latent_t = read_f32(&format!("latent_t_{seed}_{m}x{n}.bin"));
+    let design = generate_feasible_design((m, n), &latent_t, brush, false).unwrap();
+    design.visualize();
+    println!("{:?}", DesignMetrics::of(&design, &latent_t));
+    let disagreement = LatentDisagreement::of(&design, &latent_t);
+    println!(
+        "disagreeing pixels: {}, score: {:.4}, primary: {:.4}, resolving: {:.4}, free: {:.4}, other: {:.4}",
+        disagreement.map.iter().filter(|d| **d).count(),
+        disagreement.score,
+        disagreement.primary,
+        disagreement.resolving,
+        disagreement.free,
+        disagreement.other,
+    );
+
+    // a 4 pixel high bar across the grid and a 3x6 block below it
+    let (m, n) = (12, 12);
+    let solid: Vec<bool> = (0..m * n)
+        .map(|k| {
+            let (i, j) = (k / n, k % n);
+            (1..=4).contains(&i) | ((8..=10).contains(&i) & (3..=8).contains(&j))
+        })
+        .collect();
+    let metrics = DesignMetrics::new((m, n), &solid, &vec![-1.0; m * n]);
+    let expected = DesignMetrics {
+        fill_factor: 66.0 / 144.0,
+        solid_components: 2,
+        void_components: 2,
+        enclosed_voids: 0,
+        perimeter: 24 + 18,
+        min_feature_width: Some(3),
+        min_gap_width: Some(1),
+        latent_agreement: 78.0 / 144.0,
+    };
+    println!("{metrics:?}");
+    println!("hand-built design as expected: {}", metrics == expected);
+}
+
+/// Quality metrics of a design, computed on the full grid.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DesignMetrics {
+    /// fraction of solid pixels.
+    pub fill_factor: f32,
+    /// number of 4-connected solid components.
+    pub solid_components: usize,
+    /// number of 4-connected void components.
+    pub void_components: usize,
+    /// number of void components that do not touch the edge of the grid.
+    pub enclosed_voids: usize,
+    /// number of pixel edges between solid and void pixels.
+    pub perimeter: usize,
+    /// the smallest solid feature width in pixels (see `min_width`).
+    pub min_feature_width: Option<usize>,
+    /// the smallest gap between solid features in pixels (see `min_width`).
+    pub min_gap_width: Option<usize>,
+    /// fraction of pixels whose assignment matches the sign of the latent (see `latent_agreement`).
+    pub latent_agreement: f32,
+}
+
+impl DesignMetrics {
+    /// The metrics of the `solid` pixels on a grid of `shape`.
+    pub fn new(shape: (usize, usize), solid: &[bool], latent_t: &[f32]) -> Self {
+        let void: Vec<bool> = solid.iter().map(|s| !s).collect();
+        Self {
+            fill_factor: fill_factor(solid),
+            solid_components: count_components(shape, solid),
+            void_components: count_components(shape, &void),
+            enclosed_voids: count_enclosed_components(shape, &void),
+            perimeter: perimeter(shape, solid),
+            min_feature_width: min_width(shape, solid),
+            min_gap_width: min_width(shape, &void),
+            latent_agreement: latent_agreement(solid, latent_t),
+        }
+    }
+
+    /// The metrics of the solid layer of `design` (unassigned pixels count as void).
+    pub fn of(design: &Design, latent_t: &[f32]) -> Self {
+        Self::new(design.shape, &design[Layer::Solid], latent_t)
+    }
+}
+
+pub fn fill_factor(solid: &[bool]) -> f32 {
+    if solid.is_empty() {
+        return 0.0;
+    }
+    solid.iter().filter(|s| **s).count() as f32 / solid.len() as f32
+}
+
+/// Label the 4-connected components of `mask` (`None` outside the mask).
+///
+/// Returns the label of every pixel and the number of components.
+pub fn label_components(shape: (usize, usize), mask: &[bool]) -> (Vec<Option<usize>>, usize) {
+    let (m, n) = shape;
+    let mut labels = vec![None; m * n];
+    let mut count = 0;
+    let mut stack = Vec::new();
+    for start in 0..m * n {
+        if !mask[start] | labels[start].is_some() {
+            continue;
+        }
+        labels[start] = Some(count);
+        stack.push(start);
+        while let Some(k) = stack.pop() {
+            let (i, j) = (k / n, k % n);
+            let neighbours = [
+                (i > 0, k.wrapping_sub(n)),
+                (i + 1 < m, k + n),
+                (j > 0, k.wrapping_sub(1)),
+                (j + 1 < n, k + 1),
+            ];
+            for (inside, neighbour) in neighbours {
+                if inside && mask[neighbour] && labels[neighbour].is_none() {
+                    labels[neighbour] = Some(count);
+                    stack.push(neighbour);
+                }
+            }
+        }
+        count += 1;
+    }
+    (labels, count)
+}
+
+pub fn count_components(shape: (usize, usize), mask: &[bool]) -> usize {
+    let (_, count) = label_components(shape, mask);
+    count
+}
+
+/// The number of components of `mask` that do not touch the edge of the grid.
+pub fn count_enclosed_components(shape: (usize, usize), mask: &[bool]) -> usize {
+    let (m, n) = shape;
+    let (labels, count) = label_components(shape, mask);
+    let mut touches_edge = vec![false; count];
+    for (k, label) in labels.iter().enumerate() {
+        let (i, j) = (k / n, k % n);
+        if (i == 0) | (j == 0) | (i + 1 == m) | (j + 1 == n) {
+            if let Some(label) = label {
+                touches_edge[*label] = true;
+            }
+        }
+    }
+    touches_edge.iter().filter(|t| !**t).count()
+}
+
+/// The number of pixel edges between solid and void pixels (the edge of the
+/// grid is not counted).
+pub fn perimeter(shape: (usize, usize), solid: &[bool]) -> usize {
+    let (m, n) = shape;
+    let mut perimeter = 0;
+    for k in 0..m * n {
+        let (i, j) = (k / n, k % n);
+        if (i + 1 < m) && (solid[k] != solid[k + n]) {
+            perimeter += 1;
+        }
+        if (j + 1 < n) && (solid[k] != solid[k + 1]) {
+            perimeter += 1;
+        }
+    }
+    perimeter
+}
+
+/// The smallest width of the features in `mask`, in pixels.
+///
+/// This is the largest diameter of a circular brush (see `BrushShape::Circle`)
+/// such that the mask is the union of the brushes (of that and every smaller
+/// diameter) that fit inside it, i.e. the largest circular brush that could
+/// have generated it (so sharp corners limit it to 3). As in the generator,
+/// brushes may extend beyond the edge of the grid. Returns `None` for an empty
+/// mask; a full mask measures as wide as the grid.
+///
+/// Where the brushes fit and what they cover both follow from a distance
+/// transform, so every diameter costs the same, however wide.
+pub fn min_width(shape: (usize, usize), mask: &[bool]) -> Option<usize> {
+    let (m, n) = shape;
+    if !mask.iter().any(|b| *b) {
+        return None;
+    }
+    // brushes of odd diameter are centered on their origin pixel, those of even
+    // diameter on its upper left corner (see `Brush::ellipse`): a brush fits
+    // where its center is further than its radius from any pixel outside the mask.
+    let outside: Vec<bool> = mask.iter().map(|b| !b).collect();
+    let to_outside = [
+        squared_distance_transform(shape, &outside, 0.0),
+        squared_distance_transform(shape, &outside, -0.5),
+    ];
+    let mut width = 0;
+    for diameter in 1..=m.max(n) {
+        let radius2 = (diameter * diameter) as f32 / 4.0;
+        let even = diameter % 2 == 0;
+        let centers: Vec<bool> = to_outside[even as usize]
+            .iter()
+            .zip(mask.iter())
+            .map(|(d, b)| *b & (*d > radius2))
+            .collect();
+        if !centers.iter().any(|c| *c) {
+            break;
+        }
+        let shift = if even { 0.5 } else { 0.0 };
+        let to_centers = squared_distance_transform(shape, &centers, shift);
+        if (0..m * n).any(|k| mask[k] & (to_centers[k] > radius2)) {
+            break;
+        }
+        width = diameter;
+    }
+    Some(width)
+}
+
+/// The fraction of pixels that are solid where `latent_t` is positive and void
+/// where it is negative (pixels where it is zero agree either way).
+pub fn latent_agreement(solid: &[bool], latent_t: &[f32]) -> f32 {
+    if solid.is_empty() {
+        return 0.0;
+    }
+    let agreeing = solid
+        .iter()
+        .zip(latent_t.iter())
+        .filter(|(s, l)| (**l == 0.0) | (**s == (**l > 0.0)))
+        .count();
+    agreeing as f32 / solid.len() as f32
+}
+
+/// Where (and how much) a design overrides the sign of its latent.
+///
+/// The score weighs every disagreeing pixel by the magnitude of its latent,
+/// relative to the total magnitude of the latent. It is split by the kind of
+/// touch that assigned the pixel (see `Provenance::touch_kinds`), so that
+/// `primary + resolving + free + other == score`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LatentDisagreement {
+    /// pixels that are solid where the latent is negative or void where it is positive.
+    pub map: Vec<bool>,
+    pub score: f32,
+    /// the part of `score` assigned by primary touches.
+    pub primary: f32,
+    /// the part of `score` assigned by resolving touches.
+    pub resolving: f32,
+    /// the part of `score` assigned by free touches.
+    pub free: f32,
+    /// the part of `score` assigned otherwise (e.g. by seed touches) or unassigned.
+    pub other: f32,
+}
+
+impl LatentDisagreement {
+    /// The disagreement of the `solid` pixels, assigned by touches of `touch_kind`.
+    pub fn new(solid: &[bool], touch_kind: &[Option<TouchKind>], latent_t: &[f32]) -> Self {
+        let map: Vec<bool> = solid
+            .iter()
+            .zip(latent_t.iter())
+            .map(|(s, l)| (*l != 0.0) & (*s != (*l > 0.0)))
+            .collect();
+        let total: f32 = latent_t.iter().map(|l| l.abs()).sum();
+        let mut disagreement = Self {
+            map,
+            score: 0.0,
+            primary: 0.0,
+            resolving: 0.0,
+            free: 0.0,
+            other: 0.0,
+        };
+        if total == 0.0 {
+            return disagreement;
+        }
+        for k in (0..solid.len()).filter(|k| disagreement.map[*k]) {
+            let weight = latent_t[k].abs() / total;
+            disagreement.score += weight;
+            match touch_kind[k] {
+                Some(TouchKind::Primary) => disagreement.primary += weight,
+                Some(TouchKind::Resolving) => disagreement.resolving += weight,
+                Some(TouchKind::Free) => disagreement.free += weight,
+                None => disagreement.other += weight,
+            }
+        }
+        disagreement
+    }
+
+    /// The disagreement of the solid layer of `design` with `latent_t`.
+    pub fn of(design: &Design, latent_t: &[f32]) -> Self {
+        Self::new(
+            &design[Layer::Solid],
+            &design.provenance.touch_kinds(),
+            latent_t,
+        )
+    }
+}
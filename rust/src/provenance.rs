@@ -0,0 +1,189 @@
+use super::array::read_f32;
+use super::brush_io::{write_png, BrushFileError};
+use super::brushes::Brush;
+use super::design::TouchKind;
+use super::generator::{generate_feasible_design_with_options, GeneratorOptions};
+
+pub fn test_provenance() {
+    let seed = 42;
+    let (m, n) = (30, 30);
+    let brush = Brush::notched_square(5, 1);
+    let latent_t = read_f32(&format!("latent_t_{seed}_{m}x{n}.bin"));
+    let (design, _) = generate_feasible_design_with_options(
+        (m, n),
+        &latent_t,
+        brush,
+        &GeneratorOptions::default(),
+    )
+    .unwrap();
+    design.visualize();
+    let provenance = &design.provenance;
+    println!("{} iterations", provenance.iterations);
+    let kinds = provenance.kind_array();
+    for (code, name) in KIND_NAMES.iter().enumerate() {
+        let count = kinds.iter().filter(|c| **c as usize == code).count();
+        println!("{name}: {count} pixels");
+    }
+
+    let dir = std::env::temp_dir();
+    let kind_path = dir.join("provenance_kind.png");
+    let iteration_path = dir.join("provenance_iteration.png");
+    provenance
+        .write_kind_image(kind_path.to_str().unwrap(), (m, n))
+        .unwrap();
+    provenance
+        .write_iteration_image(iteration_path.to_str().unwrap(), (m, n))
+        .unwrap();
+    println!("wrote {kind_path:?} and {iteration_path:?}");
+}
+
+/// The names of the codes of `Provenance::kind_array`.
+pub const KIND_NAMES: [&str; 7] = [
+    "unassigned",
+    "primary void",
+    "primary solid",
+    "resolving void",
+    "resolving solid",
+    "free void",
+    "free solid",
+];
+
+/// The colors of the codes of `Provenance::kind_array` in `write_kind_image`:
+/// void pixels light and solid pixels dark, gray for primary touches, blue for
+/// resolving touches and green for free touches; red when unassigned.
+const KIND_COLORS: [[u8; 3]; 7] = [
+    [255, 0, 0],
+    [224, 224, 224],
+    [64, 64, 64],
+    [160, 192, 255],
+    [0, 48, 160],
+    [160, 255, 160],
+    [0, 128, 0],
+];
+
+/// The touch that first assigned a pixel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PixelOrigin {
+    /// the number of touches added before it (see `Provenance::iterations`).
+    pub iteration: usize,
+    pub kind: TouchKind,
+    /// whether it was a solid touch.
+    pub solid: bool,
+}
+
+/// Which step of the generator first assigned each pixel of a design (see
+/// `Design::provenance`).
+///
+/// Unassigned and inactive pixels, and pixels assigned by seed touches (see
+/// `Design::stamp_void_touch`), have no origin.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Provenance {
+    pub origins: Vec<Option<PixelOrigin>>,
+    /// the number of touches added (with `Design::add_void_touch` or
+    /// `Design::add_solid_touch`); free touches taken after a
+    /// touch share its iteration.
+    pub iterations: usize,
+    /// whether the void side of the design holds the solid touches (see `Design::invert`).
+    pub inverted: bool,
+}
+
+impl Provenance {
+    pub fn new(size: usize) -> Self {
+        Self {
+            origins: vec![None; size],
+            iterations: 0,
+            inverted: false,
+        }
+    }
+
+    /// Record a pixel assigned by a touch of `kind` in the current iteration,
+    /// unless it already has an origin.
+    pub fn record(&mut self, pixel: usize, kind: TouchKind) {
+        if self.origins[pixel].is_some() {
+            return;
+        }
+        self.origins[pixel] = Some(PixelOrigin {
+            iteration: self.iterations,
+            kind,
+            solid: self.inverted,
+        });
+    }
+
+    /// The kind of touch that assigned each pixel, or `None` for pixels without origin.
+    pub fn touch_kinds(&self) -> Vec<Option<TouchKind>> {
+        self.origins.iter().map(|o| o.map(|o| o.kind)).collect()
+    }
+
+    /// The iteration that assigned each pixel, or -1 for pixels without origin.
+    pub fn iteration_array(&self) -> Vec<i64> {
+        self.origins
+            .iter()
+            .map(|o| o.map_or(-1, |o| o.iteration as i64))
+            .collect()
+    }
+
+    /// The kind of touch that assigned each pixel as a code (see `KIND_NAMES`):
+    /// 0 without origin, then 1 and 2 for primary void and solid touches, 3 and
+    /// 4 for resolving touches and 5 and 6 for free touches.
+    pub fn kind_array(&self) -> Vec<u8> {
+        self.origins
+            .iter()
+            .map(|o| match o {
+                None => 0,
+                Some(o) => {
+                    let kind = match o.kind {
+                        TouchKind::Primary => 1,
+                        TouchKind::Resolving => 3,
+                        TouchKind::Free => 5,
+                    };
+                    kind + o.solid as u8
+                }
+            })
+            .collect()
+    }
+
+    /// Write the kind of touch that assigned each pixel as a PNG image (see `KIND_COLORS`).
+    pub fn write_kind_image(
+        &self,
+        path: &str,
+        shape: (usize, usize),
+    ) -> Result<(), BrushFileError> {
+        let colors: Vec<[u8; 3]> = self
+            .kind_array()
+            .iter()
+            .map(|c| KIND_COLORS[*c as usize])
+            .collect();
+        write_rgb_png(path, shape, &colors)
+    }
+
+    /// Write the iteration that assigned each pixel as a PNG image, from black
+    /// (first) to white (last); red for pixels without origin.
+    pub fn write_iteration_image(
+        &self,
+        path: &str,
+        shape: (usize, usize),
+    ) -> Result<(), BrushFileError> {
+        let last = self.iterations.max(2) - 1;
+        let colors: Vec<[u8; 3]> = self
+            .origins
+            .iter()
+            .map(|o| match o {
+                None => [255, 0, 0],
+                Some(o) => {
+                    let gray = (255 * o.iteration.min(last) / last) as u8;
+                    [gray, gray, gray]
+                }
+            })
+            .collect();
+        write_rgb_png(path, shape, &colors)
+    }
+}
+
+fn write_rgb_png(
+    path: &str,
+    shape: (usize, usize),
+    colors: &[[u8; 3]],
+) -> Result<(), BrushFileError> {
+    let data: Vec<u8> = colors.iter().flatten().copied().collect();
+    write_png(path, shape, png::ColorType::Rgb, &data)
+}
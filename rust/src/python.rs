@@ -1,13 +1,30 @@
 use super::array::parse_f32;
 use super::brushes::Brush;
 use super::debug::print_profiler_summary as print_profiler_summary_rs;
+use super::design_rules::{DesignRuleError, DesignRules};
 use super::generator::generate_feasible_design as generate_feasible_design_rs;
+use super::generator::{
+    generate_feasible_design_with_brushes, generate_feasible_design_with_options, GeneratorOptions,
+};
+use super::layers::Layer;
+use super::metrics::DesignMetrics;
+use super::repair::repair_design as repair_design_rs;
+use pyo3::exceptions::PyValueError;
 use pyo3::prelude::{pyfunction, pymodule, PyModule, PyResult, Python};
+use pyo3::types::PyDict;
 use pyo3::wrap_pyfunction;
 
 #[pymodule]
 fn inverse_design_rs(_py: Python, m: &PyModule) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(generate_feasible_design, m)?)?;
+    m.add_function(wrap_pyfunction!(generate_feasible_design_with_rules, m)?)?;
+    m.add_function(wrap_pyfunction!(
+        generate_feasible_design_with_provenance,
+        m
+    )?)?;
+    m.add_function(wrap_pyfunction!(design_rule_brushes, m)?)?;
+    m.add_function(wrap_pyfunction!(repair_design, m)?)?;
+    m.add_function(wrap_pyfunction!(design_metrics, m)?)?;
     m.add_function(wrap_pyfunction!(print_profiler_summary, m)?)?;
     Ok(())
 }
@@ -19,15 +36,188 @@ pub fn generate_feasible_design(
     brush_shape: (usize, usize),
     brush_bytes: Vec<u8>,
     verbose: bool,
-) -> (Vec<bool>, Vec<bool>, Vec<bool>) {
+) -> PyResult<(Vec<bool>, Vec<bool>, Vec<bool>)> {
     let latent_t = parse_f32(&latent_t_bytes);
     let brush = Brush::from_f32_mask(brush_shape, &parse_f32(&brush_bytes));
-    let design = generate_feasible_design_rs(latent_t_shape, &latent_t, brush, verbose);
-    return (
-        design.void,
-        design.void_touch_existing,
-        design.solid_touch_existing,
-    );
+    let design = generate_feasible_design_rs(latent_t_shape, &latent_t, brush, verbose)
+        .map_err(|e| PyValueError::new_err(e.to_string()))?;
+    let mut layers = design.layers;
+    Ok((
+        layers.take(Layer::Void),
+        layers.take(Layer::VoidTouchExisting),
+        layers.take(Layer::SolidTouchExisting),
+    ))
+}
+
+/// The void pixels, void touches and solid touches, and the iteration and kind
+/// of touch that assigned each pixel.
+type DesignWithProvenance = (Vec<bool>, Vec<bool>, Vec<bool>, Vec<i64>, Vec<u8>);
+
+/// Like `generate_feasible_design`, also returning the iteration (-1 when
+/// unassigned) and the kind of touch (see `Provenance::kind_array`) that
+/// assigned each pixel.
+#[pyfunction]
+pub fn generate_feasible_design_with_provenance(
+    latent_t_shape: (usize, usize),
+    latent_t_bytes: Vec<u8>,
+    brush_shape: (usize, usize),
+    brush_bytes: Vec<u8>,
+    verbose: bool,
+) -> PyResult<DesignWithProvenance> {
+    let latent_t = parse_f32(&latent_t_bytes);
+    let brush = Brush::from_f32_mask(brush_shape, &parse_f32(&brush_bytes));
+    let options = GeneratorOptions {
+        verbose,
+        ..GeneratorOptions::default()
+    };
+    let (design, _) =
+        generate_feasible_design_with_options(latent_t_shape, &latent_t, brush, &options)
+            .map_err(|e| PyValueError::new_err(e.to_string()))?;
+    let provenance = design.provenance;
+    let mut layers = design.layers;
+    Ok((
+        layers.take(Layer::Void),
+        layers.take(Layer::VoidTouchExisting),
+        layers.take(Layer::SolidTouchExisting),
+        provenance.iteration_array(),
+        provenance.kind_array(),
+    ))
+}
+
+/// Generate a design whose brushes enforce `min_width` and `min_gap` (in the units of `pitch`).
+#[pyfunction]
+pub fn generate_feasible_design_with_rules(
+    latent_t_shape: (usize, usize),
+    latent_t_bytes: Vec<u8>,
+    min_width: f32,
+    min_gap: f32,
+    pitch: f32,
+    brush_shape: &str,
+    verbose: bool,
+) -> PyResult<(Vec<bool>, Vec<bool>, Vec<bool>)> {
+    let latent_t = parse_f32(&latent_t_bytes);
+    let rules = design_rules(min_width, min_gap, pitch, brush_shape)?;
+    rules
+        .check()
+        .map_err(|e| PyValueError::new_err(e.to_string()))?;
+    let (void_brush, solid_brush) = (rules.void_brush().unwrap(), rules.solid_brush().unwrap());
+    let options = GeneratorOptions {
+        verbose,
+        ..GeneratorOptions::default()
+    };
+    let (design, _) = generate_feasible_design_with_brushes(
+        latent_t_shape,
+        &latent_t,
+        void_brush,
+        solid_brush,
+        &options,
+    )
+    .map_err(|e| PyValueError::new_err(e.to_string()))?;
+    let mut layers = design.layers;
+    Ok((
+        layers.take(Layer::Void),
+        layers.take(Layer::VoidTouchExisting),
+        layers.take(Layer::SolidTouchExisting),
+    ))
+}
+
+/// The shape and the mask of a brush.
+type BrushMask = ((usize, usize), Vec<bool>);
+
+/// The void and solid brushes (shape and mask) for the design rules, and the
+/// enforced min width and min gap.
+#[pyfunction]
+pub fn design_rule_brushes(
+    min_width: f32,
+    min_gap: f32,
+    pitch: f32,
+    brush_shape: &str,
+) -> PyResult<(BrushMask, BrushMask, (f32, f32))> {
+    let rules = design_rules(min_width, min_gap, pitch, brush_shape)?;
+    let enforced = rules
+        .enforced()
+        .map_err(|e| PyValueError::new_err(e.to_string()))?;
+    let (void_brush, solid_brush) = (rules.void_brush().unwrap(), rules.solid_brush().unwrap());
+    Ok((
+        (void_brush.shape, void_brush.mask()),
+        (solid_brush.shape, solid_brush.mask()),
+        (enforced.min_width, enforced.min_gap),
+    ))
+}
+
+fn design_rules(
+    min_width: f32,
+    min_gap: f32,
+    pitch: f32,
+    brush_shape: &str,
+) -> PyResult<DesignRules> {
+    let shape = brush_shape
+        .parse()
+        .map_err(|e: DesignRuleError| PyValueError::new_err(e.to_string()))?;
+    Ok(DesignRules {
+        shape,
+        ..DesignRules::new(min_width, min_gap, pitch)
+    })
+}
+
+/// The void pixels, void touches and solid touches, and the number of flipped pixels.
+type RepairedDesign = (Vec<bool>, Vec<bool>, Vec<bool>, usize);
+
+#[pyfunction]
+pub fn repair_design(
+    target_shape: (usize, usize),
+    target_bytes: Vec<u8>,
+    brush_shape: (usize, usize),
+    brush_bytes: Vec<u8>,
+    verbose: bool,
+) -> PyResult<RepairedDesign> {
+    let target = parse_f32(&target_bytes);
+    let brush = Brush::from_f32_mask(brush_shape, &parse_f32(&brush_bytes));
+    let options = GeneratorOptions {
+        verbose,
+        ..GeneratorOptions::default()
+    };
+    let (design, report) = repair_design_rs(target_shape, &target, brush, &options)
+        .map_err(|e| PyValueError::new_err(e.to_string()))?;
+    let mut layers = design.layers;
+    Ok((
+        layers.take(Layer::Void),
+        layers.take(Layer::VoidTouchExisting),
+        layers.take(Layer::SolidTouchExisting),
+        report.flipped_pixels,
+    ))
+}
+
+/// The metrics of a design (see `DesignMetrics`) as a dict; widths are `None`
+/// when there are no solid (or no void) pixels.
+#[pyfunction]
+pub fn design_metrics<'py>(
+    py: Python<'py>,
+    shape: (usize, usize),
+    solid: Vec<bool>,
+    latent_t_bytes: Vec<u8>,
+) -> PyResult<&'py PyDict> {
+    let latent_t = parse_f32(&latent_t_bytes);
+    let (m, n) = shape;
+    if (solid.len() != m * n) | (latent_t.len() != m * n) {
+        return Err(PyValueError::new_err(format!(
+            "expected {} values for the design and the latent, got {} and {}.",
+            m * n,
+            solid.len(),
+            latent_t.len()
+        )));
+    }
+    let metrics = DesignMetrics::new(shape, &solid, &latent_t);
+    let dict = PyDict::new(py);
+    dict.set_item("fill_factor", metrics.fill_factor)?;
+    dict.set_item("solid_components", metrics.solid_components)?;
+    dict.set_item("void_components", metrics.void_components)?;
+    dict.set_item("enclosed_voids", metrics.enclosed_voids)?;
+    dict.set_item("perimeter", metrics.perimeter)?;
+    dict.set_item("min_feature_width", metrics.min_feature_width)?;
+    dict.set_item("min_gap_width", metrics.min_gap_width)?;
+    dict.set_item("latent_agreement", metrics.latent_agreement)?;
+    Ok(dict)
 }
 
 #[pyfunction]
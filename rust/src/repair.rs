@@ -0,0 +1,187 @@
+use super::array::read_f32;
+use super::brushes::Brush;
+use super::debug::Profiler;
+use super::design::Design;
+use super::generator::{
+    generate_feasible_design_with_options, GeneratorError, GeneratorOptions, GeneratorReport,
+};
+use super::layers::Layer;
+
+pub fn test_repair() {
+    let (m, n) = (30, 30);
+    let brush = Brush::notched_square(5, 1);
+    let latent_t = read_f32(&format!("latent_t_42_{m}x{n}.bin"));
+    // a thresholded latent with some single-pixel defects sprinkled in
+    let target: Vec<f32> = latent_t
+        .iter()
+        .enumerate()
+        .map(|(k, l)| {
+            let solid = (*l > 0.0) ^ (k % 37 == 0);
+            if solid {
+                1.0
+            } else {
+                0.0
+            }
+        })
+        .collect();
+    let (design, report) =
+        repair_design((m, n), &target, brush, &GeneratorOptions::default()).unwrap();
+    design.visualize();
+    println!("flipped pixels: {}", report.flipped_pixels);
+}
+
+pub struct RepairReport {
+    /// number of pixels whose assignment differs from the target mask.
+    pub flipped_pixels: usize,
+    pub generator: GeneratorReport,
+}
+
+/// Project a (binary or grayscale) target mask onto the set of feasible designs.
+///
+/// Target values above 0.5 are considered solid. The latent ranking is the
+/// signed distance to the target boundary (positive inside solid), so the
+/// generator starts touching deep inside the target features and only has to
+/// make decisions near their edges.
+pub fn repair_design(
+    shape: (usize, usize),
+    target: &[f32],
+    brush: Brush,
+    options: &GeneratorOptions,
+) -> Result<(Design, RepairReport), GeneratorError> {
+    let profiler = Profiler::start("repair_design");
+    let latent_t = target_to_latent(shape, target);
+    let (design, generator) =
+        generate_feasible_design_with_options(shape, &latent_t, brush, options)?;
+    let flipped_pixels = target
+        .iter()
+        .zip(design[Layer::Solid].iter())
+        .filter(|(t, s)| (**t > 0.5) != **s)
+        .count();
+    profiler.stop();
+    Ok((
+        design,
+        RepairReport {
+            flipped_pixels,
+            generator,
+        },
+    ))
+}
+
+/// Signed distance to the target boundary, refined by the grayscale value.
+pub fn target_to_latent(shape: (usize, usize), target: &[f32]) -> Vec<f32> {
+    let solid: Vec<bool> = target.iter().map(|t| *t > 0.5).collect();
+    let void: Vec<bool> = solid.iter().map(|s| !s).collect();
+    let distance_to_void = distance_transform(shape, &void);
+    let distance_to_solid = distance_transform(shape, &solid);
+    signed_distance_latent(&solid, &distance_to_void, &distance_to_solid, target)
+}
+
+fn signed_distance_latent(
+    solid: &[bool],
+    distance_to_void: &[f32],
+    distance_to_solid: &[f32],
+    target: &[f32],
+) -> Vec<f32> {
+    let mut latent_t = Vec::new();
+    for k in 0..solid.len() {
+        // pixels right at the boundary have distance 1; the grayscale value
+        // shifts them by less than half a pixel.
+        let refinement = target[k].clamp(0.0, 1.0) - 0.5;
+        let distance = if solid[k] {
+            distance_to_void[k] - 0.5
+        } else {
+            0.5 - distance_to_solid[k]
+        };
+        latent_t.push(distance + refinement);
+    }
+    latent_t
+}
+
+/// Euclidean distance from every pixel to the nearest `true` pixel in `mask`.
+///
+/// Uses the separable algorithm of Felzenszwalb & Huttenlocher. Without any
+/// `true` pixels, all distances are larger than the diagonal of the grid.
+pub fn distance_transform(shape: (usize, usize), mask: &[bool]) -> Vec<f32> {
+    let squared = squared_distance_transform(shape, mask, 0.0);
+    squared.iter().map(|d| d.sqrt()).collect()
+}
+
+/// Squared Euclidean distance from every pixel, moved by `shift` along both
+/// axes (e.g. -0.5 for its upper left corner), to the nearest `true` pixel in
+/// `mask` (see `distance_transform`).
+pub fn squared_distance_transform(shape: (usize, usize), mask: &[bool], shift: f32) -> Vec<f32> {
+    let (m, n) = shape;
+    let far = ((m + n) * (m + n)) as f32;
+    let mut squared = vec![far; m * n];
+
+    // along the columns, the nearest `true` pixel above or below
+    let mut nearest: Vec<Option<usize>> = vec![None; n];
+    for i in 0..m {
+        for j in 0..n {
+            if mask[i * n + j] {
+                nearest[j] = Some(i);
+            }
+            if let Some(p) = nearest[j] {
+                let delta = i as f32 + shift - p as f32;
+                squared[i * n + j] = delta * delta;
+            }
+        }
+    }
+    nearest.fill(None);
+    for i in (0..m).rev() {
+        for j in 0..n {
+            if mask[i * n + j] {
+                nearest[j] = Some(i);
+            }
+            if let Some(p) = nearest[j] {
+                let delta = i as f32 + shift - p as f32;
+                squared[i * n + j] = squared[i * n + j].min(delta * delta);
+            }
+        }
+    }
+    for i in 0..m {
+        let row = squared[i * n..(i + 1) * n].to_vec();
+        let transformed = distance_transform_1d(&row, shift);
+        squared[i * n..(i + 1) * n].copy_from_slice(&transformed);
+    }
+    squared
+}
+
+/// `d[q] = min_p (q + shift - p)^2 + f[p]`, from the lower envelope of the parabolas.
+fn distance_transform_1d(f: &[f32], shift: f32) -> Vec<f32> {
+    let n = f.len();
+    let mut d = vec![0.0; n];
+    if n == 0 {
+        return d;
+    }
+    let mut v = vec![0usize; n];
+    let mut z = vec![0.0f32; n + 1];
+    let mut k = 0;
+    z[0] = f32::NEG_INFINITY;
+    z[1] = f32::INFINITY;
+    let intersection = |q: usize, p: usize| -> f32 {
+        let (qf, pf) = (q as f32, p as f32);
+        ((f[q] + qf * qf) - (f[p] + pf * pf)) / (2.0 * qf - 2.0 * pf)
+    };
+    for q in 1..n {
+        let mut s = intersection(q, v[k]);
+        while s <= z[k] {
+            k -= 1;
+            s = intersection(q, v[k]);
+        }
+        k += 1;
+        v[k] = q;
+        z[k] = s;
+        z[k + 1] = f32::INFINITY;
+    }
+    k = 0;
+    for (q, dq) in d.iter_mut().enumerate() {
+        let x = q as f32 + shift;
+        while z[k + 1] < x {
+            k += 1;
+        }
+        let delta = x - v[k] as f32;
+        *dq = delta * delta + f[v[k]];
+    }
+    d
+}
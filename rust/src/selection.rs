@@ -0,0 +1,238 @@
+use super::array::{read_f32, XorShift};
+use super::brushes::Brush;
+use super::design::Design;
+use super::generator::{generate_feasible_design_with_options, GeneratorError, GeneratorOptions};
+use super::layers::Layer;
+
+pub fn test_selection() {
+    let (m, n) = (30, 30);
+    let latent_t = read_f32(&format!("latent_t_42_{m}x{n}.bin"));
+    let selections = vec![
+        Selection::LargestLatent,
+        Selection::Alternating,
+        Selection::Stochastic {
+            temperature: 0.1,
+            seed: 42,
+        },
+        Selection::DistanceWeighted { length_scale: 5.0 },
+        Selection::LargestRemainingConfidence,
+    ];
+    for selection in selections.into_iter() {
+        let options = GeneratorOptions {
+            selection,
+            ..GeneratorOptions::default()
+        };
+        let brush = Brush::notched_square(5, 1);
+        let (design, report) =
+            generate_feasible_design_with_options((m, n), &latent_t, brush, &options).unwrap();
+        let disagreeing = latent_t
+            .iter()
+            .zip(design[Layer::Solid].iter())
+            .filter(|(l, s)| (**l > 0.0) != **s)
+            .count();
+        println!(
+            "{selection:?}: feasible={}, unresolved touches={}, disagreeing pixels={disagreeing}",
+            design.is_feasible(),
+            report.unresolved_touches.len(),
+        );
+    }
+
+    let options = GeneratorOptions {
+        selection: Selection::Stochastic {
+            temperature: 0.0,
+            seed: 42,
+        },
+        ..GeneratorOptions::default()
+    };
+    let brush = Brush::notched_square(5, 1);
+    if let Err(error) = generate_feasible_design_with_options((m, n), &latent_t, brush, &options) {
+        println!("{error}");
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Void,
+    Solid,
+}
+
+/// The best remaining touch on one side.
+///
+/// `latent` is the latent value as seen from that side, i.e. `-latent_t` for
+/// void touches and `latent_t` for solid touches: larger means more confident.
+#[derive(Debug, Clone, Copy)]
+pub struct Candidate {
+    pub pos: (usize, usize),
+    pub latent: f32,
+}
+
+/// Decides whether the next touch is the best void or the best solid candidate.
+pub trait SelectionStrategy {
+    fn select(
+        &mut self,
+        void: &Candidate,
+        solid: &Candidate,
+        design: &Design,
+        latent_t: &[f32],
+    ) -> Side;
+}
+
+/// The built-in selection strategies, configurable through `GeneratorOptions`.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Selection {
+    /// take the candidate with the largest latent magnitude (the python reference).
+    #[default]
+    LargestLatent,
+    /// alternate between void and solid touches.
+    Alternating,
+    /// pick solid with probability `sigmoid((solid - void) / temperature)`.
+    Stochastic { temperature: f32, seed: u64 },
+    /// subtract `distance / length_scale` to the previous touch from the latent.
+    DistanceWeighted { length_scale: f32 },
+    /// sum the latent over the pixels the touch would still assign.
+    LargestRemainingConfidence,
+}
+
+impl Selection {
+    /// The strategy for this selection, if its parameters are valid.
+    pub fn strategy(&self) -> Result<Box<dyn SelectionStrategy>, GeneratorError> {
+        let valid = match *self {
+            Self::Stochastic { temperature, .. } => temperature > 0.0,
+            Self::DistanceWeighted { length_scale } => length_scale > 0.0,
+            _ => true,
+        };
+        if !valid {
+            return Err(GeneratorError::InvalidSelection { selection: *self });
+        }
+        let strategy: Box<dyn SelectionStrategy> = match *self {
+            Self::LargestLatent => Box::new(LargestLatent),
+            Self::Alternating => Box::new(Alternating { next: Side::Void }),
+            Self::Stochastic { temperature, seed } => Box::new(Stochastic {
+                temperature,
+                rng: XorShift::new(seed),
+            }),
+            Self::DistanceWeighted { length_scale } => Box::new(DistanceWeighted {
+                length_scale,
+                previous: None,
+            }),
+            Self::LargestRemainingConfidence => Box::new(LargestRemainingConfidence),
+        };
+        Ok(strategy)
+    }
+}
+
+pub struct LargestLatent;
+
+impl SelectionStrategy for LargestLatent {
+    fn select(&mut self, void: &Candidate, solid: &Candidate, _: &Design, _: &[f32]) -> Side {
+        if solid.latent > void.latent {
+            return Side::Solid;
+        }
+        Side::Void
+    }
+}
+
+pub struct Alternating {
+    pub next: Side,
+}
+
+impl SelectionStrategy for Alternating {
+    fn select(&mut self, _: &Candidate, _: &Candidate, _: &Design, _: &[f32]) -> Side {
+        let side = self.next;
+        self.next = match side {
+            Side::Void => Side::Solid,
+            Side::Solid => Side::Void,
+        };
+        side
+    }
+}
+
+pub struct Stochastic {
+    pub temperature: f32,
+    pub rng: XorShift,
+}
+
+impl SelectionStrategy for Stochastic {
+    fn select(&mut self, void: &Candidate, solid: &Candidate, _: &Design, _: &[f32]) -> Side {
+        let p_solid = 1.0 / (1.0 + ((void.latent - solid.latent) / self.temperature).exp());
+        if self.rng.uniform() < p_solid {
+            return Side::Solid;
+        }
+        Side::Void
+    }
+}
+
+pub struct DistanceWeighted {
+    pub length_scale: f32,
+    pub previous: Option<(usize, usize)>,
+}
+
+impl DistanceWeighted {
+    /// The penalty of `pos`, with distances measured on the grid of `design`.
+    ///
+    /// It is subtracted (rather than dividing the latent by a weight), so that
+    /// far touches lose priority whatever the sign of their latent.
+    fn penalty(&self, pos: (usize, usize), design: &Design) -> f32 {
+        let (_, n) = design.shape;
+        let distance = match self.previous {
+            None => 0.0,
+            Some((pi, pj)) => {
+                let a = design.grid.position(pos.0 * n + pos.1);
+                let b = design.grid.position(pi * n + pj);
+                let distance2: f32 = a
+                    .iter()
+                    .zip(b.iter())
+                    .map(|(a, b)| (*a as f32 - *b as f32).powi(2))
+                    .sum();
+                distance2.sqrt()
+            }
+        };
+        distance / self.length_scale
+    }
+}
+
+impl SelectionStrategy for DistanceWeighted {
+    fn select(&mut self, void: &Candidate, solid: &Candidate, design: &Design, _: &[f32]) -> Side {
+        let void_priority = void.latent - self.penalty(void.pos, design);
+        let solid_priority = solid.latent - self.penalty(solid.pos, design);
+        if solid_priority > void_priority {
+            self.previous = Some(solid.pos);
+            return Side::Solid;
+        }
+        self.previous = Some(void.pos);
+        Side::Void
+    }
+}
+
+pub struct LargestRemainingConfidence;
+
+impl SelectionStrategy for LargestRemainingConfidence {
+    fn select(
+        &mut self,
+        void: &Candidate,
+        solid: &Candidate,
+        design: &Design,
+        latent_t: &[f32],
+    ) -> Side {
+        let mut void_confidence: f32 = 0.0;
+        design.stencils_at(void.pos).brush.for_each(void.pos, |k| {
+            if !(design[Layer::VoidPixelExisting][k] | design[Layer::VoidPixelImpossible][k]) {
+                void_confidence -= latent_t[k];
+            }
+        });
+        let mut solid_confidence: f32 = 0.0;
+        design
+            .solid_stencils_at(solid.pos)
+            .brush
+            .for_each(solid.pos, |k| {
+                if !(design[Layer::SolidPixelExisting][k] | design[Layer::SolidPixelImpossible][k])
+                {
+                    solid_confidence += latent_t[k];
+                }
+            });
+        if solid_confidence > void_confidence {
+            return Side::Solid;
+        }
+        Side::Void
+    }
+}
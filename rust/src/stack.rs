@@ -0,0 +1,290 @@
+use super::array::read_f32;
+use super::brushes::Brush;
+use super::debug::Profiler;
+use super::design::{Design, InactivePolicy};
+use super::generator::{
+    generate_feasible_design_with_active_region, GeneratorError, GeneratorOptions, GeneratorReport,
+    UnresolvedStrategy,
+};
+use super::layers::Layer;
+use super::repair::distance_transform;
+use std::error::Error;
+use std::fmt;
+
+pub fn test_stack() {
+    let seed = 42;
+    let (m, n) = (30, 30);
+    let latent_t = read_f32(&format!("latent_t_{seed}_{m}x{n}.bin"));
+    // the shallow etch prefers less material than the full etch
+    let shallow_latent_t = latent_t.iter().map(|l| l - 0.5).collect();
+    let rules = vec![StackRule::Enclosure {
+        inner: 1,
+        outer: 0,
+        margin: 1.0,
+    }];
+    let (stack, _) = generate_feasible_stack(
+        (m, n),
+        &[latent_t, shallow_latent_t],
+        vec![Brush::notched_square(5, 1), Brush::notched_square(3, 0)],
+        &rules,
+        &GeneratorOptions::default(),
+    )
+    .unwrap();
+    for design in stack.designs.iter() {
+        design.visualize();
+    }
+    println!(
+        "feasible: {}, rule violations: {}",
+        stack.is_feasible(),
+        stack.check_rules().len()
+    );
+}
+
+/// A rule between the solid pixels of two layers of a stack.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StackRule {
+    /// solid pixels of `inner` are solid in `outer` as well.
+    Containment { inner: usize, outer: usize },
+    /// solid pixels of `inner` are surrounded by at least `margin` pixels of solid `outer`.
+    Enclosure {
+        inner: usize,
+        outer: usize,
+        margin: f32,
+    },
+    /// solid pixels of `a` and `b` are more than `spacing` pixels apart (never overlap).
+    Exclusion { a: usize, b: usize, spacing: f32 },
+}
+
+impl StackRule {
+    /// The layers involved, in the order (inner, outer) or (a, b).
+    pub fn layers(&self) -> (usize, usize) {
+        match self {
+            Self::Containment { inner, outer } => (*inner, *outer),
+            Self::Enclosure { inner, outer, .. } => (*inner, *outer),
+            Self::Exclusion { a, b, .. } => (*a, *b),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StackError {
+    /// the number of latents and brushes differ.
+    LayerCountMismatch {
+        latents: usize,
+        brushes: usize,
+    },
+    /// a rule refers to a layer that is not in the stack.
+    InvalidLayer {
+        rule: usize,
+        layer: usize,
+    },
+    /// the rules force pixels of a layer to be solid as well as void.
+    ConflictingRules {
+        layer: usize,
+    },
+    Generator {
+        layer: usize,
+        error: GeneratorError,
+    },
+}
+
+impl fmt::Display for StackError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::LayerCountMismatch { latents, brushes } => write!(
+                f,
+                "got {latents} latents for {brushes} brushes, expected one latent per brush."
+            ),
+            Self::InvalidLayer { rule, layer } => {
+                write!(
+                    f,
+                    "rule {rule} refers to layer {layer}, which does not exist."
+                )
+            }
+            Self::ConflictingRules { layer } => write!(
+                f,
+                "the rules force pixels of layer {layer} to be both solid and void."
+            ),
+            Self::Generator { layer, error } => write!(f, "layer {layer}: {error}"),
+        }
+    }
+}
+
+impl Error for StackError {}
+
+/// A stack of designs on the same grid, e.g. the etch layers of a device.
+pub struct Stack {
+    pub shape: (usize, usize),
+    pub designs: Vec<Design>,
+    pub rules: Vec<StackRule>,
+}
+
+impl Stack {
+    /// Solid pixels of a layer, including the pixels the rules fixed to solid.
+    pub fn solid(&self, layer: usize) -> Vec<bool> {
+        let design = &self.designs[layer];
+        let fixed = design.inactive_policy == InactivePolicy::Solid;
+        design[Layer::Solid]
+            .iter()
+            .zip(design.active.iter())
+            .map(|(s, a)| *s | (!a & fixed))
+            .collect()
+    }
+
+    /// Void pixels of a layer, including the pixels the rules fixed to void.
+    pub fn void(&self, layer: usize) -> Vec<bool> {
+        let design = &self.designs[layer];
+        let fixed = design.inactive_policy == InactivePolicy::Void;
+        design[Layer::Void]
+            .iter()
+            .zip(design.active.iter())
+            .map(|(v, a)| *v | (!a & fixed))
+            .collect()
+    }
+
+    pub fn is_feasible(&self) -> bool {
+        self.designs.iter().all(|d| d.is_feasible())
+    }
+
+    /// Indices of the rules that the stack does not satisfy.
+    pub fn check_rules(&self) -> Vec<usize> {
+        let violated = self
+            .rules
+            .iter()
+            .enumerate()
+            .filter(|(_, rule)| {
+                let (a, b) = rule.layers();
+                let (solid_a, solid_b) = (self.solid(a), self.solid(b));
+                let required = match rule {
+                    StackRule::Containment { .. } => solid_a.clone(),
+                    StackRule::Enclosure { margin, .. } => dilate(self.shape, &solid_a, *margin),
+                    StackRule::Exclusion { spacing, .. } => dilate(self.shape, &solid_a, *spacing),
+                };
+                let forbidden = matches!(rule, StackRule::Exclusion { .. });
+                required
+                    .iter()
+                    .zip(solid_b.iter())
+                    .any(|(r, s)| *r & (*s == forbidden))
+            })
+            .map(|(i, _)| i)
+            .collect();
+        violated
+    }
+}
+
+/// Generate a feasible design per layer such that the stack satisfies `rules`.
+///
+/// Layers are generated in order, each driven by its own latent. The rules
+/// between a layer and the layers generated before it fix some of its pixels
+/// (to void, or to solid); these pixels are left out of the design region.
+/// A layer can have pixels fixed to void or to solid, but not both.
+pub fn generate_feasible_stack(
+    shape: (usize, usize),
+    latents_t: &[Vec<f32>],
+    brushes: Vec<Brush>,
+    rules: &[StackRule],
+    options: &GeneratorOptions,
+) -> Result<(Stack, Vec<GeneratorReport>), StackError> {
+    let profiler = Profiler::start("generate_feasible_stack");
+    let (m, n) = shape;
+    if latents_t.len() != brushes.len() {
+        return Err(StackError::LayerCountMismatch {
+            latents: latents_t.len(),
+            brushes: brushes.len(),
+        });
+    }
+    for (rule, layer) in rules.iter().enumerate().flat_map(|(i, r)| {
+        let (a, b) = r.layers();
+        [(i, a), (i, b)]
+    }) {
+        if layer >= brushes.len() {
+            return Err(StackError::InvalidLayer { rule, layer });
+        }
+    }
+
+    let mut stack = Stack {
+        shape,
+        designs: Vec::new(),
+        rules: rules.to_vec(),
+    };
+    let mut reports = Vec::new();
+    for (layer, (latent_t, brush)) in latents_t.iter().zip(brushes).enumerate() {
+        let mut fixed_void = vec![false; m * n];
+        let mut fixed_solid = vec![false; m * n];
+        for rule in rules.iter() {
+            let (a, b) = rule.layers();
+            let (fixed, mask) = match rule {
+                StackRule::Containment { .. } if (a == layer) & (b < layer) => {
+                    (&mut fixed_void, invert(&stack.solid(b)))
+                }
+                StackRule::Containment { .. } if (b == layer) & (a < layer) => {
+                    (&mut fixed_solid, stack.solid(a))
+                }
+                StackRule::Enclosure { margin, .. } if (a == layer) & (b < layer) => (
+                    &mut fixed_void,
+                    dilate(shape, &invert(&stack.solid(b)), *margin),
+                ),
+                StackRule::Enclosure { margin, .. } if (b == layer) & (a < layer) => {
+                    (&mut fixed_solid, dilate(shape, &stack.solid(a), *margin))
+                }
+                StackRule::Exclusion { spacing, .. } if (a == layer) & (b < layer) => {
+                    (&mut fixed_void, dilate(shape, &stack.solid(b), *spacing))
+                }
+                StackRule::Exclusion { spacing, .. } if (b == layer) & (a < layer) => {
+                    (&mut fixed_void, dilate(shape, &stack.solid(a), *spacing))
+                }
+                _ => continue,
+            };
+            for (f, x) in fixed.iter_mut().zip(mask.iter()) {
+                *f |= *x;
+            }
+        }
+
+        let any_void = fixed_void.iter().any(|x| *x);
+        let any_solid = fixed_solid.iter().any(|x| *x);
+        let policy = match (any_void, any_solid) {
+            (true, true) => return Err(StackError::ConflictingRules { layer }),
+            (true, false) => InactivePolicy::Void,
+            (false, true) => InactivePolicy::Solid,
+            (false, false) => InactivePolicy::Forbidden,
+        };
+        let active: Vec<bool> = fixed_void
+            .iter()
+            .zip(fixed_solid.iter())
+            .map(|(v, s)| !(v | s))
+            .collect();
+        let layer_options = GeneratorOptions {
+            unresolved_strategy: if any_void | any_solid {
+                UnresolvedStrategy::Backtrack
+            } else {
+                options.unresolved_strategy
+            },
+            ..options.clone()
+        };
+        let (design, report) = generate_feasible_design_with_active_region(
+            shape,
+            latent_t,
+            brush,
+            &active,
+            policy,
+            &layer_options,
+        )
+        .map_err(|error| StackError::Generator { layer, error })?;
+        stack.designs.push(design);
+        reports.push(report);
+    }
+    profiler.stop();
+    Ok((stack, reports))
+}
+
+/// Pixels within `radius` of a `true` pixel of `mask`.
+fn dilate(shape: (usize, usize), mask: &[bool], radius: f32) -> Vec<bool> {
+    distance_transform(shape, mask)
+        .iter()
+        .map(|d| *d <= radius)
+        .collect()
+}
+
+fn invert(mask: &[bool]) -> Vec<bool> {
+    mask.iter().map(|x| !x).collect()
+}
@@ -0,0 +1,352 @@
+use super::array::read_f32;
+use super::brushes::Brush;
+use super::design::Design;
+use super::generator::generate_feasible_design;
+use super::layers::{Layer, Layers};
+use super::status::Status;
+use super::verification::Violation;
+
+pub fn test_state() {
+    let seed = 42;
+    let (m, n) = (30, 30);
+    let brush = Brush::notched_square(5, 1);
+    let latent_t = read_f32(&format!("latent_t_{seed}_{m}x{n}.bin"));
+    let design = generate_feasible_design((m, n), &latent_t, brush, false).unwrap();
+
+    let packed = design.packed().unwrap();
+    println!("{} bytes -> {} bytes", 14 * m * n, m * n);
+    println!("feasible design: {}", round_trip(&design, &packed));
+
+    // halfway through generation, with required pixels and touches pending
+    let mut design = Design::new((m, n), Brush::notched_square(5, 1));
+    let mut order: Vec<usize> = (0..m * n).collect();
+    order.sort_by(|a, b| latent_t[*b].abs().total_cmp(&latent_t[*a].abs()));
+    let mut pending_touches = Vec::new();
+    for k in order.into_iter().take(20) {
+        let pos = (k / n, k % n);
+        let solid = latent_t[k] > 0.0;
+        let layer = |layer: Layer| side(layer, solid);
+        if design[layer(Layer::VoidTouchInvalid)][k] | design[layer(Layer::VoidTouchExisting)][k] {
+            continue;
+        }
+        let (_, resolving_touches) = if solid {
+            design.add_solid_touch(pos)
+        } else {
+            design.add_void_touch(pos)
+        };
+        let resolving = resolving_touches
+            .into_iter()
+            .map(|(i, j)| (i * n + j, solid));
+        pending_touches.extend(resolving);
+    }
+    // flag the touches that would still resolve the required pixels
+    for (t, solid) in pending_touches.into_iter() {
+        let layer = |layer: Layer| side(layer, solid);
+        design.layers[layer(Layer::VoidTouchRequired)][t] = !(design
+            [layer(Layer::VoidTouchInvalid)][t]
+            | design[layer(Layer::VoidTouchExisting)][t]);
+    }
+    let packed = design.packed().unwrap();
+    let required_pixels = packed
+        .states
+        .iter()
+        .filter(|s| {
+            (s.void_pixel() == PixelState::Required) | (s.solid_pixel() == PixelState::Required)
+        })
+        .count();
+    let required_touches = packed
+        .states
+        .iter()
+        .filter(|s| {
+            (s.void_touch() == TouchState::Required) | (s.solid_touch() == TouchState::Required)
+        })
+        .count();
+    println!(
+        "design in progress: {}, required pixels: {required_pixels}, required touches: {required_touches}",
+        round_trip(&design, &packed)
+    );
+
+    // a pixel that exists on both sides cannot be packed
+    let mut broken = design.clone();
+    broken.layers[Layer::SolidPixelExisting][0] = true;
+    broken.layers[Layer::Solid][0] = true;
+    broken.layers[Layer::VoidPixelExisting][0] = true;
+    broken.layers[Layer::Void][0] = true;
+    match broken.packed() {
+        Ok(_) => println!("inconsistent design packed"),
+        Err(violation) => println!("inconsistent design: {violation}"),
+    }
+}
+
+/// Whether `packed` unpacks to the layers of `design`, with the same views.
+fn round_trip(design: &Design, packed: &PackedDesign) -> String {
+    let layers = packed.to_layers();
+    let same_layers = Layer::ALL
+        .iter()
+        .all(|layer| layers[*layer] == design[*layer]);
+    let same_views = [
+        (packed.design_view(), design.design_view()),
+        (packed.void_pixel_view(), design.void_pixel_view()),
+        (packed.solid_pixel_view(), design.solid_pixel_view()),
+        (packed.void_touches_view(), design.void_touches_view()),
+        (packed.solid_touches_view(), design.solid_touches_view()),
+    ]
+    .iter()
+    .all(|(a, b)| a.iter().zip(b.iter()).all(|(x, y)| *x as u8 == *y as u8));
+    format!("round trip: {same_layers}, same views: {same_views}")
+}
+
+/// State of a pixel in the void (or solid) pixel layers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PixelState {
+    Possible = 0,
+    Impossible = 1,
+    Existing = 2,
+    Required = 3,
+}
+
+/// State of a touch in the void (or solid) touch layers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TouchState {
+    Valid = 0,
+    Invalid = 1,
+    Existing = 2,
+    Required = 3,
+}
+
+impl PixelState {
+    fn from_bits(bits: u8) -> Self {
+        match bits & 0b11 {
+            0 => Self::Possible,
+            1 => Self::Impossible,
+            2 => Self::Existing,
+            _ => Self::Required,
+        }
+    }
+}
+
+impl TouchState {
+    fn from_bits(bits: u8) -> Self {
+        match bits & 0b11 {
+            0 => Self::Valid,
+            1 => Self::Invalid,
+            2 => Self::Existing,
+            _ => Self::Required,
+        }
+    }
+}
+
+impl From<PixelState> for Status {
+    fn from(state: PixelState) -> Self {
+        match state {
+            PixelState::Possible => Status::PixelPossible,
+            PixelState::Impossible => Status::PixelImpossible,
+            PixelState::Existing => Status::PixelExisting,
+            PixelState::Required => Status::PixelRequired,
+        }
+    }
+}
+
+impl From<TouchState> for Status {
+    fn from(state: TouchState) -> Self {
+        match state {
+            TouchState::Valid => Status::TouchValid,
+            TouchState::Invalid => Status::TouchInvalid,
+            TouchState::Existing => Status::TouchExisting,
+            TouchState::Required => Status::TouchRequired,
+        }
+    }
+}
+
+/// The state of a pixel packed into a single byte:
+/// bits 0-1: void pixel, bits 2-3: solid pixel, bits 4-5: void touch, bits 6-7: solid touch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PackedState(u8);
+
+impl PackedState {
+    /// Pack a pixel state, rejecting contradictory pixel states.
+    pub fn new(
+        void_pixel: PixelState,
+        solid_pixel: PixelState,
+        void_touch: TouchState,
+        solid_touch: TouchState,
+    ) -> Result<Self, (PixelState, PixelState)> {
+        let exists_opposite = |a: PixelState, b: PixelState| {
+            (a == PixelState::Existing) & (b != PixelState::Impossible)
+        };
+        if exists_opposite(void_pixel, solid_pixel) | exists_opposite(solid_pixel, void_pixel) {
+            return Err((void_pixel, solid_pixel));
+        }
+        let bits = (void_pixel as u8)
+            | ((solid_pixel as u8) << 2)
+            | ((void_touch as u8) << 4)
+            | ((solid_touch as u8) << 6);
+        Ok(Self(bits))
+    }
+
+    pub fn void_pixel(self) -> PixelState {
+        PixelState::from_bits(self.0)
+    }
+
+    pub fn solid_pixel(self) -> PixelState {
+        PixelState::from_bits(self.0 >> 2)
+    }
+
+    pub fn void_touch(self) -> TouchState {
+        TouchState::from_bits(self.0 >> 4)
+    }
+
+    pub fn solid_touch(self) -> TouchState {
+        TouchState::from_bits(self.0 >> 6)
+    }
+
+    pub fn design(self) -> Status {
+        if self.void_pixel() == PixelState::Existing {
+            return Status::Void;
+        } else if self.solid_pixel() == PixelState::Existing {
+            return Status::Solid;
+        }
+        Status::Unassigned
+    }
+
+    /// The same state with void and solid swapped.
+    pub fn invert(self) -> Self {
+        let pixels = ((self.0 & 0b0011) << 2) | ((self.0 & 0b1100) >> 2);
+        let touches = ((self.0 & 0b0011_0000) << 2) | ((self.0 & 0b1100_0000) >> 2);
+        Self(pixels | touches)
+    }
+
+    pub fn bits(self) -> u8 {
+        self.0
+    }
+}
+
+/// A design with one `PackedState` per pixel instead of 14 boolean layers.
+#[derive(Clone)]
+pub struct PackedDesign {
+    pub shape: (usize, usize),
+    pub states: Vec<PackedState>,
+}
+
+impl PackedDesign {
+    /// Pack `layers`, rejecting the states that `Design::check_invariants`
+    /// reports as violations for a single pixel.
+    pub fn from_layers(shape: (usize, usize), layers: &Layers) -> Result<Self, Violation> {
+        let (m, n) = shape;
+        let mut states = Vec::with_capacity(m * n);
+        for k in 0..m * n {
+            let pos = (k / n, k % n);
+            let void_pixel = pixel_state(layers, k, pos, false)?;
+            let solid_pixel = pixel_state(layers, k, pos, true)?;
+            let void_touch = touch_state(layers, k, pos, false)?;
+            let solid_touch = touch_state(layers, k, pos, true)?;
+            let state = PackedState::new(void_pixel, solid_pixel, void_touch, solid_touch)
+                .map_err(|(void, _)| Violation::ExistingNotExclusive {
+                    pos,
+                    solid: void != PixelState::Existing,
+                })?;
+            states.push(state);
+        }
+        Ok(Self { shape, states })
+    }
+
+    pub fn to_layers(&self) -> Layers {
+        let (m, n) = self.shape;
+        let mut layers = Layers::new(m * n);
+        for (k, state) in self.states.iter().enumerate() {
+            for (solid, pixel, touch) in [
+                (false, state.void_pixel(), state.void_touch()),
+                (true, state.solid_pixel(), state.solid_touch()),
+            ] {
+                let layer = |layer: Layer| side(layer, solid);
+                layers[layer(Layer::Void)][k] = pixel == PixelState::Existing;
+                layers[layer(Layer::VoidPixelImpossible)][k] = pixel == PixelState::Impossible;
+                layers[layer(Layer::VoidPixelExisting)][k] = pixel == PixelState::Existing;
+                layers[layer(Layer::VoidPixelRequired)][k] = pixel == PixelState::Required;
+                layers[layer(Layer::VoidTouchRequired)][k] = touch == TouchState::Required;
+                layers[layer(Layer::VoidTouchInvalid)][k] = touch == TouchState::Invalid;
+                layers[layer(Layer::VoidTouchExisting)][k] = touch == TouchState::Existing;
+            }
+        }
+        layers
+    }
+
+    pub fn invert(&mut self) {
+        for state in self.states.iter_mut() {
+            *state = state.invert();
+        }
+    }
+
+    pub fn design_view(&self) -> Vec<Status> {
+        self.states.iter().map(|s| s.design()).collect()
+    }
+
+    pub fn void_pixel_view(&self) -> Vec<Status> {
+        self.states.iter().map(|s| s.void_pixel().into()).collect()
+    }
+
+    pub fn solid_pixel_view(&self) -> Vec<Status> {
+        self.states.iter().map(|s| s.solid_pixel().into()).collect()
+    }
+
+    pub fn void_touches_view(&self) -> Vec<Status> {
+        self.states.iter().map(|s| s.void_touch().into()).collect()
+    }
+
+    pub fn solid_touches_view(&self) -> Vec<Status> {
+        self.states.iter().map(|s| s.solid_touch().into()).collect()
+    }
+}
+
+impl Design {
+    pub fn packed(&self) -> Result<PackedDesign, Violation> {
+        PackedDesign::from_layers(self.shape, &self.layers)
+    }
+}
+
+/// The void (or solid) layer for `layer`.
+fn side(layer: Layer, solid: bool) -> Layer {
+    if solid {
+        return layer.opposite();
+    }
+    layer
+}
+
+fn pixel_state(
+    layers: &Layers,
+    k: usize,
+    pos: (usize, usize),
+    solid: bool,
+) -> Result<PixelState, Violation> {
+    let impossible = layers[side(Layer::VoidPixelImpossible, solid)][k];
+    let existing = layers[side(Layer::VoidPixelExisting, solid)][k];
+    let required = layers[side(Layer::VoidPixelRequired, solid)][k];
+    if layers[side(Layer::Void, solid)][k] != existing {
+        return Err(Violation::DesignMismatch { pos, solid });
+    }
+    match (impossible, existing, required) {
+        (false, false, false) => Ok(PixelState::Possible),
+        (true, false, false) => Ok(PixelState::Impossible),
+        (false, true, false) => Ok(PixelState::Existing),
+        (false, false, true) => Ok(PixelState::Required),
+        _ => Err(Violation::AmbiguousPixel { pos, solid }),
+    }
+}
+
+fn touch_state(
+    layers: &Layers,
+    k: usize,
+    pos: (usize, usize),
+    solid: bool,
+) -> Result<TouchState, Violation> {
+    let required = layers[side(Layer::VoidTouchRequired, solid)][k];
+    let invalid = layers[side(Layer::VoidTouchInvalid, solid)][k];
+    let existing = layers[side(Layer::VoidTouchExisting, solid)][k];
+    match (required, invalid, existing) {
+        (false, false, false) => Ok(TouchState::Valid),
+        (true, false, false) => Ok(TouchState::Required),
+        (false, true, false) => Ok(TouchState::Invalid),
+        (false, false, true) => Ok(TouchState::Existing),
+        _ => Err(Violation::AmbiguousTouch { pos, solid }),
+    }
+}
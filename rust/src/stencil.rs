@@ -0,0 +1,269 @@
+use super::brushes::Brush;
+use super::grid::Grid;
+
+/// A brush precomputed for a grid of a given shape.
+///
+/// Holds the brush offsets both as linear offsets into the flattened grid and
+/// as runs along the last axis, so stamping does not need per-offset bounds
+/// checks for positions far enough from the border. Positions are given in the
+/// planar view of the grid (see `Grid`), so the same stencil API serves
+/// planar and voxel designs.
+#[derive(Clone)]
+pub struct Stencil {
+    pub grid: Grid,
+    /// the planar `(rows, columns)` view of `grid`.
+    pub shape: (usize, usize),
+    /// row-major sorted linear offsets.
+    pub offsets: Vec<isize>,
+    /// row-major sorted runs of consecutive offsets along the last axis.
+    pub runs: Vec<Run>,
+    /// smallest and largest offset along every axis.
+    pub bounds: Vec<(i32, i32)>,
+}
+
+/// Consecutive stencil offsets along the last axis of the grid.
+#[derive(Clone)]
+pub struct Run {
+    /// offset along every axis but the last one.
+    pub lead: Vec<i32>,
+    /// `lead` as an offset in planar rows.
+    pub rows: isize,
+    /// offset of the first element along the last axis.
+    pub column: i32,
+    pub len: usize,
+}
+
+impl Stencil {
+    pub fn new(brush: &Brush, shape: (usize, usize)) -> Self {
+        Self::from_offsets(&brush.brush, shape)
+    }
+
+    /// The stencil of the brush mirrored through its origin.
+    pub fn reflected(brush: &Brush, shape: (usize, usize)) -> Self {
+        let offsets: Vec<(i32, i32)> = brush.brush.iter().map(|(i, j)| (-i, -j)).collect();
+        Self::from_offsets(&offsets, shape)
+    }
+
+    pub fn from_offsets(offsets: &[(i32, i32)], shape: (usize, usize)) -> Self {
+        let offsets: Vec<Vec<i32>> = offsets.iter().map(|(i, j)| vec![*i, *j]).collect();
+        Self::from_grid_offsets(&offsets, Grid::plane(shape))
+    }
+
+    /// A stencil from offsets with one coordinate per axis of `grid`.
+    pub fn from_grid_offsets(offsets: &[Vec<i32>], grid: Grid) -> Self {
+        let ndim = grid.ndim();
+        assert!(
+            offsets.iter().all(|o| o.len() == ndim),
+            "stencil offsets need one coordinate per grid axis"
+        );
+        let mut sorted = offsets.to_vec();
+        sorted.sort();
+        sorted.dedup();
+
+        let mut runs: Vec<Run> = Vec::new();
+        for offset in sorted.iter() {
+            let (lead, column) = (&offset[..ndim - 1], offset[ndim - 1]);
+            match runs.last_mut() {
+                Some(run) if (run.lead == lead) & (run.column + run.len as i32 == column) => {
+                    run.len += 1
+                }
+                _ => runs.push(Run {
+                    lead: lead.to_vec(),
+                    rows: lead
+                        .iter()
+                        .zip(grid.dims.iter())
+                        .fold(0, |k, (o, size)| k * (*size as isize) + (*o as isize)),
+                    column,
+                    len: 1,
+                }),
+            }
+        }
+
+        let bounds = (0..ndim)
+            .map(|a| {
+                let min = sorted.iter().map(|o| o[a]).min().unwrap_or(0);
+                let max = sorted.iter().map(|o| o[a]).max().unwrap_or(0);
+                (min, max)
+            })
+            .collect();
+        let offsets = sorted.iter().map(|o| grid.offset(o)).collect();
+
+        Self {
+            shape: grid.rows(),
+            grid,
+            offsets,
+            runs,
+            bounds,
+        }
+    }
+
+    /// Whether every offset lands inside the grid when centered at `pos`.
+    pub fn is_interior(&self, pos: (usize, usize)) -> bool {
+        let (_, n) = self.shape;
+        let (jmin, jmax) = self.bounds[self.bounds.len() - 1];
+        let j = pos.1 as i32;
+        if (j + jmin < 0) | (n as i32 <= j + jmax) {
+            return false;
+        }
+        let mut row = pos.0;
+        let lead = self.grid.dims.len() - 1;
+        for (size, (min, max)) in self.grid.dims[..lead]
+            .iter()
+            .zip(self.bounds[..lead].iter())
+            .rev()
+        {
+            let i = (row % size) as i32;
+            if (i + min < 0) | (*size as i32 <= i + max) {
+                return false;
+            }
+            row /= size;
+        }
+        true
+    }
+
+    /// Whether the leading coordinates of planar row `row` shifted by `lead` stay in the grid.
+    fn lead_in_bounds(&self, row: usize, lead: &[i32]) -> bool {
+        let mut row = row;
+        for (size, offset) in self.grid.dims.iter().zip(lead.iter()).rev() {
+            let i = (row % size) as i32 + offset;
+            if (i < 0) | (*size as i32 <= i) {
+                return false;
+            }
+            row /= size;
+        }
+        true
+    }
+
+    /// Whether `pos` lies in the bounding box of the stencil centered at `center`.
+    pub fn box_contains(&self, center: (usize, usize), pos: (usize, usize)) -> bool {
+        let (_, n) = self.shape;
+        let (mut a, mut b) = (center.0 * n + center.1, pos.0 * n + pos.1);
+        for (size, (min, max)) in self.grid.dims.iter().zip(self.bounds.iter()).rev() {
+            let d = (b % size) as i32 - (a % size) as i32;
+            if (d < *min) | (*max < d) {
+                return false;
+            }
+            a /= size;
+            b /= size;
+        }
+        true
+    }
+
+    /// Call `f(start, length)` for each (clipped) run of linear indices at `pos`.
+    pub fn for_each_run<F: FnMut(usize, usize)>(&self, pos: (usize, usize), mut f: F) {
+        let (_, n) = self.shape;
+        let j = pos.1 as i32;
+        if self.is_interior(pos) {
+            let base = (pos.0 * n + pos.1) as isize;
+            for run in self.runs.iter() {
+                let start = base + run.rows * (n as isize) + (run.column as isize);
+                f(start as usize, run.len);
+            }
+            return;
+        }
+        for run in self.runs.iter() {
+            if !self.lead_in_bounds(pos.0, &run.lead) {
+                continue;
+            }
+            let row = pos.0 as isize + run.rows;
+            let start = (j + run.column).max(0);
+            let stop = (j + run.column + run.len as i32).min(n as i32);
+            if start < stop {
+                f(row as usize * n + start as usize, (stop - start) as usize);
+            }
+        }
+    }
+
+    /// Call `f` for each linear index covered by the stencil at `pos`.
+    pub fn for_each<F: FnMut(usize)>(&self, pos: (usize, usize), mut f: F) {
+        if self.is_interior(pos) {
+            let (_, n) = self.shape;
+            let base = (pos.0 * n + pos.1) as isize;
+            for offset in self.offsets.iter() {
+                f((base + offset) as usize);
+            }
+            return;
+        }
+        self.for_each_run(pos, |start, len| {
+            for k in start..start + len {
+                f(k);
+            }
+        });
+    }
+
+    /// Whether `f` holds for every linear index covered by the stencil at `pos`.
+    pub fn all<F: FnMut(usize) -> bool>(&self, pos: (usize, usize), mut f: F) -> bool {
+        if self.is_interior(pos) {
+            let (_, n) = self.shape;
+            let base = (pos.0 * n + pos.1) as isize;
+            return self
+                .offsets
+                .iter()
+                .all(|offset| f((base + offset) as usize));
+        }
+        let mut result = true;
+        self.for_each_run(pos, |start, len| {
+            if result {
+                result = (start..start + len).all(&mut f);
+            }
+        });
+        result
+    }
+
+    /// Number of grid pixels covered by the stencil at `pos`.
+    pub fn count(&self, pos: (usize, usize)) -> usize {
+        if self.is_interior(pos) {
+            return self.offsets.len();
+        }
+        let mut count = 0;
+        self.for_each_run(pos, |_, len| count += len);
+        count
+    }
+
+    /// Grid positions covered by the stencil at `pos` (like `Brush::at`).
+    pub fn at(&self, pos: (usize, usize)) -> Vec<(usize, usize)> {
+        let (_, n) = self.shape;
+        let mut positions = Vec::with_capacity(self.offsets.len());
+        self.for_each(pos, |k| positions.push((k / n, k % n)));
+        positions
+    }
+
+    pub fn apply<T: Copy>(&self, array: &mut [T], pos: (usize, usize), value: T) {
+        self.for_each_run(pos, |start, len| array[start..start + len].fill(value));
+    }
+
+    /// Stamp `values[l]` into `arrays[l]` for every layer `l` at once.
+    pub fn multi_apply<T: Copy>(&self, arrays: &mut [&mut [T]], pos: (usize, usize), values: &[T]) {
+        self.for_each_run(pos, |start, len| {
+            for (array, value) in arrays.iter_mut().zip(values.iter()) {
+                array[start..start + len].fill(*value);
+            }
+        });
+    }
+}
+
+/// The stencils a `Design` stamps with.
+#[derive(Clone)]
+pub struct Stencils {
+    pub brush: Stencil,
+    pub reflected_brush: Stencil,
+    /// the opposite touches overlapping a touch at the origin, per opposite brush.
+    pub big_brushes: Vec<Stencil>,
+    pub very_big_brush: Stencil,
+}
+
+impl Stencils {
+    pub fn new(
+        brush: &Brush,
+        big_brushes: &[Brush],
+        very_big_brush: &Brush,
+        shape: (usize, usize),
+    ) -> Self {
+        Self {
+            brush: Stencil::new(brush, shape),
+            reflected_brush: Stencil::reflected(brush, shape),
+            big_brushes: big_brushes.iter().map(|b| Stencil::new(b, shape)).collect(),
+            very_big_brush: Stencil::new(very_big_brush, shape),
+        }
+    }
+}
@@ -0,0 +1,251 @@
+use super::array::read_f32;
+use super::brushes::{compute_very_big_brush, Brush, Rasterization};
+use super::debug::{now, profiler, since, Profiler};
+use super::design::Design;
+use super::generator::{
+    check_latent, generate_feasible_design_with_options, sort_indices_by_value, GeneratorError,
+    GeneratorOptions, GeneratorReport, UnresolvedStrategy,
+};
+use super::layers::Layer;
+use super::metrics::{latent_agreement, LatentDisagreement};
+use super::warm_start::complete_from_touches;
+use rayon::prelude::{IntoParallelRefIterator, ParallelIterator};
+
+pub fn test_tiling() {
+    let seed = 42;
+    let brush = Brush::notched_square(5, 1);
+    let latent_t = read_f32(&format!("latent_t_{seed}_300x300.bin"));
+    // the fixture mirrored into a 600x600 latent, to see how tiling scales
+    let mirrored: Vec<f32> = (0..600 * 600)
+        .map(|k| {
+            let (i, j) = (k / 600, k % 600);
+            let (i, j) = (i.min(599 - i), j.min(599 - j));
+            latent_t[i * 300 + j]
+        })
+        .collect();
+    let options = GeneratorOptions::default();
+    println!("{} threads", rayon::current_num_threads());
+    // a wide brush needs a wider halo along the columns than along the rows
+    let wide = Brush::rounded_rectangle((3.0, 9.0), 1.0, 0.0, Rasterization::Center);
+    for (shape, latent_t, tile_shape, brush) in [
+        ((300, 300), &latent_t, (100, 100), &brush),
+        ((600, 600), &mirrored, (150, 150), &brush),
+        ((300, 300), &latent_t, (100, 100), &wide),
+    ] {
+        let start_time = now();
+        let (design, _) =
+            generate_feasible_design_with_options(shape, latent_t, brush.clone(), &options)
+                .unwrap();
+        println!(
+            "{shape:?} brush {:?} sequential: {:.3}s, latent agreement: {:.4}, disagreement score: {:.4}",
+            brush.shape,
+            since(start_time),
+            latent_agreement(&design[Layer::Solid], latent_t),
+            LatentDisagreement::of(&design, latent_t).score
+        );
+
+        profiler().remove("tile");
+        profiler().remove("stitch_tiles");
+        let start_time = now();
+        let (tiled, report) =
+            generate_feasible_design_tiled(shape, latent_t, brush.clone(), tile_shape, &options)
+                .unwrap();
+        let tiled_time = since(start_time);
+        let different = design[Layer::Void]
+            .iter()
+            .zip(tiled[Layer::Void].iter())
+            .filter(|(a, b)| a != b)
+            .count();
+        let tiles = profiler()["tile"].clone();
+        let stitch: f32 = profiler()["stitch_tiles"].iter().sum();
+        let slowest = tiles.iter().fold(0.0f32, |a, t| a.max(*t));
+        println!(
+            "{shape:?} tiled: {tiled_time:.3}s, latent agreement: {:.4}, disagreement score: {:.4}, feasible: {}, reused touches: {}, pixels different: {different}",
+            latent_agreement(&tiled[Layer::Solid], latent_t),
+            LatentDisagreement::of(&tiled, latent_t).score,
+            tiled.is_feasible(),
+            report.reused_touches
+        );
+        // with a thread per tile, the tiles take as long as the slowest one
+        println!(
+            "  {} tiles: {:.3}s in total, slowest {slowest:.3}s; stitching: {stitch:.3}s; \
+             with a thread per tile: {:.3}s",
+            tiles.len(),
+            tiles.iter().sum::<f32>(),
+            slowest + stitch
+        );
+    }
+}
+
+/// A rectangular part of the grid: rows `i0..i1` and columns `j0..j1`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tile {
+    pub i0: usize,
+    pub i1: usize,
+    pub j0: usize,
+    pub j1: usize,
+}
+
+impl Tile {
+    pub fn shape(&self) -> (usize, usize) {
+        (self.i1 - self.i0, self.j1 - self.j0)
+    }
+
+    pub fn contains(&self, pos: (usize, usize)) -> bool {
+        let (i, j) = pos;
+        (self.i0 <= i) & (i < self.i1) & (self.j0 <= j) & (j < self.j1)
+    }
+
+    /// The tile grown by `halo` (rows, columns) pixels on every side (clipped to `shape`).
+    pub fn grow(&self, halo: (usize, usize), shape: (usize, usize)) -> Tile {
+        let (m, n) = shape;
+        Tile {
+            i0: self.i0.saturating_sub(halo.0),
+            i1: (self.i1 + halo.0).min(m),
+            j0: self.j0.saturating_sub(halo.1),
+            j1: (self.j1 + halo.1).min(n),
+        }
+    }
+
+    /// The tile shrunk by `seam` (rows, columns) pixels on every side that
+    /// borders another tile.
+    pub fn interior(&self, seam: (usize, usize), shape: (usize, usize)) -> Tile {
+        let (m, n) = shape;
+        let shrink = |lo: usize, hi: usize, size: usize, seam: usize| {
+            let lo_ = if lo == 0 { 0 } else { (lo + seam).min(hi) };
+            let hi_ = if hi == size {
+                size
+            } else {
+                hi.saturating_sub(seam).max(lo_)
+            };
+            (lo_, hi_)
+        };
+        let (i0, i1) = shrink(self.i0, self.i1, m, seam.0);
+        let (j0, j1) = shrink(self.j0, self.j1, n, seam.1);
+        Tile { i0, i1, j0, j1 }
+    }
+
+    /// Copy the part of a (row-major) array covered by the tile.
+    pub fn extract<T: Copy>(&self, shape: (usize, usize), array: &[T]) -> Vec<T> {
+        let (_, n) = shape;
+        let mut result = Vec::with_capacity((self.i1 - self.i0) * (self.j1 - self.j0));
+        for i in self.i0..self.i1 {
+            result.extend_from_slice(&array[i * n + self.j0..i * n + self.j1]);
+        }
+        result
+    }
+}
+
+/// Split a grid into tiles of (at most) `tile_shape`.
+pub fn split_into_tiles(shape: (usize, usize), tile_shape: (usize, usize)) -> Vec<Tile> {
+    let (m, n) = shape;
+    let (tm, tn) = (tile_shape.0.max(1), tile_shape.1.max(1));
+    let mut tiles = Vec::new();
+    for i0 in (0..m).step_by(tm) {
+        for j0 in (0..n).step_by(tn) {
+            tiles.push(Tile {
+                i0,
+                i1: (i0 + tm).min(m),
+                j0,
+                j1: (j0 + tn).min(n),
+            });
+        }
+    }
+    tiles
+}
+
+/// Generate a feasible design by domain decomposition.
+///
+/// The grid is split into tiles of `tile_shape`. Every tile is grown by a halo
+/// of the extent of `very_big_brush` along each axis and generated independently (in parallel). The
+/// touches each tile placed in its own core, away from the seams with its
+/// neighbours, are then used to seed a single design in which the seams are
+/// completed (with backtracking). Only the touches the seeds left open, around
+/// the seams, are regenerated, so stitching is cheap next to the tiles. If the
+/// stitched design is not feasible, the design is regenerated sequentially
+/// instead.
+pub fn generate_feasible_design_tiled(
+    shape: (usize, usize),
+    latent_t: &[f32],
+    brush: Brush,
+    tile_shape: (usize, usize),
+    options: &GeneratorOptions,
+) -> Result<(Design, GeneratorReport), GeneratorError> {
+    check_latent(shape, latent_t)?;
+    let profiler = Profiler::start("generate_feasible_design_tiled");
+    let halo = compute_very_big_brush(&brush).shape;
+    let seam = brush.shape;
+
+    let tiles = split_into_tiles(shape, tile_shape);
+    let tile_touches = tiles
+        .par_iter()
+        .map(|tile| {
+            let profiler = Profiler::start("tile");
+            let region = tile.grow(halo, shape);
+            let (_, rn) = region.shape();
+            let region_latent_t = region.extract(shape, latent_t);
+            let (design, _) = generate_feasible_design_with_options(
+                region.shape(),
+                &region_latent_t,
+                brush.clone(),
+                options,
+            )?;
+            let interior = tile.interior(seam, shape);
+            let core_touches = |touches: &Vec<bool>| -> Vec<(usize, usize)> {
+                (0..touches.len())
+                    .filter(|k| touches[*k])
+                    .map(|k| (region.i0 + k / rn, region.j0 + k % rn))
+                    .filter(|pos| interior.contains(*pos))
+                    .collect()
+            };
+            let touches = (
+                core_touches(&design[Layer::VoidTouchExisting]),
+                core_touches(&design[Layer::SolidTouchExisting]),
+            );
+            profiler.stop();
+            Ok(touches)
+        })
+        .collect::<Result<Vec<_>, GeneratorError>>()?;
+
+    let mut void_touches = Vec::new();
+    let mut solid_touches = Vec::new();
+    for (void, solid) in tile_touches.into_iter() {
+        void_touches.extend(void);
+        solid_touches.extend(solid);
+    }
+    // seed strongest latent first so that conflicting seeds keep the most
+    // confident touch: the most negative for void and the most positive for solid.
+    let void_latent_t: Vec<f32> = latent_t.iter().map(|l| -l).collect();
+    sort_indices_by_value(&mut void_touches, &void_latent_t, shape);
+    void_touches.reverse();
+    sort_indices_by_value(&mut solid_touches, latent_t, shape);
+    solid_touches.reverse();
+
+    let stitch_profiler = Profiler::start("stitch_tiles");
+    let stitch_options = GeneratorOptions {
+        unresolved_strategy: UnresolvedStrategy::Backtrack,
+        ..options.clone()
+    };
+    let stitched = complete_from_touches(
+        shape,
+        brush.clone(),
+        &void_touches,
+        &solid_touches,
+        latent_t,
+        &stitch_options,
+    );
+    stitch_profiler.stop();
+
+    let result = match stitched {
+        Ok((design, report)) if design.is_feasible() => (design, report),
+        Ok(_) | Err(GeneratorError::UnresolvedPixels { .. }) => {
+            if options.verbose {
+                println!("stitching tiles failed, generating sequentially.");
+            }
+            generate_feasible_design_with_options(shape, latent_t, brush, options)?
+        }
+        Err(error) => return Err(error),
+    };
+    profiler.stop();
+    Ok(result)
+}
@@ -0,0 +1,296 @@
+use super::array::read_f32;
+use super::brushes::Brush;
+use super::connectivity::ConnectivityRules;
+use super::debug::Profiler;
+use super::design::{for_each_touch, Design};
+use super::generator::{generate_feasible_design_with_options, GeneratorOptions};
+use super::layers::Layer;
+use std::fmt;
+
+pub fn test_verification() {
+    let seed = 42;
+    let (m, n) = (30, 30);
+    let latent_t = read_f32(&format!("latent_t_{seed}_{m}x{n}.bin"));
+    for (size, notch) in [(5, 1), (4, 1)] {
+        let options = GeneratorOptions {
+            debug: true,
+            ..GeneratorOptions::default()
+        };
+        let brush = Brush::notched_square(size, notch);
+        match generate_feasible_design_with_options((m, n), &latent_t, brush, &options) {
+            Ok((design, _)) => println!(
+                "brush ({size}, {notch}): consistent, feasible: {}",
+                design.is_feasible()
+            ),
+            Err(e) => println!("brush ({size}, {notch}): {e}"),
+        }
+    }
+}
+
+impl Design {
+    /// Pixels flagged as required (on either side) that were never assigned.
+    pub fn unresolved_required_pixels(&self) -> Vec<(usize, usize)> {
+        let (_, n) = self.shape;
+        (0..self[Layer::Void].len())
+            .filter(|k| {
+                (self[Layer::VoidPixelRequired][*k] & !self[Layer::Void][*k])
+                    | (self[Layer::SolidPixelRequired][*k] & !self[Layer::Solid][*k])
+            })
+            .map(|k| (k / n, k % n))
+            .collect()
+    }
+
+    /// Active pixels that are neither void nor solid.
+    pub fn unassigned_pixels(&self) -> Vec<(usize, usize)> {
+        let (_, n) = self.shape;
+        (0..self[Layer::Void].len())
+            .filter(|k| self.active[*k] & !(self[Layer::Void][*k] | self[Layer::Solid][*k]))
+            .map(|k| (k / n, k % n))
+            .collect()
+    }
+
+    /// Every pixel is assigned to exactly one of void or solid.
+    pub fn is_feasible(&self) -> bool {
+        let overlapping = self[Layer::Void]
+            .iter()
+            .zip(self[Layer::Solid].iter())
+            .any(|(v, s)| *v & *s);
+        !overlapping & self.unassigned_pixels().is_empty()
+    }
+}
+
+/// A component of a `Design` that breaks its `ConnectivityRules`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConnectivityViolation {
+    /// a solid component that is not anchored.
+    SolidIsland { pos: (usize, usize), pixels: usize },
+    /// a void component that is not anchored.
+    EnclosedVoid { pos: (usize, usize), pixels: usize },
+}
+
+impl fmt::Display for ConnectivityViolation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::SolidIsland {
+                pos: (i, j),
+                pixels,
+            } => write!(f, "solid island of {pixels} pixels at ({i}, {j})."),
+            Self::EnclosedVoid {
+                pos: (i, j),
+                pixels,
+            } => write!(f, "enclosed void of {pixels} pixels at ({i}, {j})."),
+        }
+    }
+}
+
+impl Design {
+    /// The components that break `rules`, each reported at its first pixel.
+    pub fn check_connectivity(&self, rules: &ConnectivityRules) -> Vec<ConnectivityViolation> {
+        let (_, n) = self.shape;
+        let mut violations = Vec::new();
+        if rules.forbid_solid_islands {
+            for component in self.floating_components(true) {
+                violations.push(ConnectivityViolation::SolidIsland {
+                    pos: (component[0] / n, component[0] % n),
+                    pixels: component.len(),
+                });
+            }
+        }
+        if rules.forbid_enclosed_voids {
+            for component in self.floating_components(false) {
+                violations.push(ConnectivityViolation::EnclosedVoid {
+                    pos: (component[0] / n, component[0] % n),
+                    pixels: component.len(),
+                });
+            }
+        }
+        violations
+    }
+}
+
+/// A broken relationship between the layers of a `Design`.
+///
+/// `solid` tells on which side (void or solid layers) the violation was found.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Violation {
+    /// the pixel is both void and solid.
+    Overlap { pos: (usize, usize) },
+    /// an inactive pixel is void or solid.
+    InactiveAssigned { pos: (usize, usize) },
+    /// the `void` (or `solid`) layer differs from the existing pixel layer.
+    DesignMismatch { pos: (usize, usize), solid: bool },
+    /// more than one of impossible/existing/required is set.
+    AmbiguousPixel { pos: (usize, usize), solid: bool },
+    /// an existing pixel is not marked impossible on the other side.
+    ExistingNotExclusive { pos: (usize, usize), solid: bool },
+    /// more than one of required/invalid/existing is set.
+    AmbiguousTouch { pos: (usize, usize), solid: bool },
+    /// an existing pixel not covered by the brush of any existing touch.
+    UnstampedPixel { pos: (usize, usize), solid: bool },
+    /// a pixel covered by the brush of an existing touch that does not exist.
+    MissingPixel { pos: (usize, usize), solid: bool },
+    /// a valid touch whose brush overlaps existing pixels of the other side.
+    ValidTouchOverlaps { pos: (usize, usize), solid: bool },
+    /// the number of valid touches covering the pixel is out of date.
+    CoverageMismatch {
+        pos: (usize, usize),
+        solid: bool,
+        expected: u32,
+        actual: u32,
+    },
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let side = |solid: &bool| if *solid { "solid" } else { "void" };
+        match self {
+            Self::Overlap { pos: (i, j) } => write!(f, "pixel ({i}, {j}) is both void and solid."),
+            Self::InactiveAssigned { pos: (i, j) } => {
+                write!(f, "inactive pixel ({i}, {j}) is void or solid.")
+            }
+            Self::DesignMismatch { pos: (i, j), solid } => write!(
+                f,
+                "{} layer at ({i}, {j}) does not match the existing {} pixels.",
+                side(solid),
+                side(solid)
+            ),
+            Self::AmbiguousPixel { pos: (i, j), solid } => write!(
+                f,
+                "{} pixel ({i}, {j}) has more than one state.",
+                side(solid)
+            ),
+            Self::ExistingNotExclusive { pos: (i, j), solid } => write!(
+                f,
+                "existing {} pixel ({i}, {j}) is not impossible on the other side.",
+                side(solid)
+            ),
+            Self::AmbiguousTouch { pos: (i, j), solid } => write!(
+                f,
+                "{} touch ({i}, {j}) has more than one state.",
+                side(solid)
+            ),
+            Self::UnstampedPixel { pos: (i, j), solid } => write!(
+                f,
+                "existing {} pixel ({i}, {j}) is not covered by an existing touch.",
+                side(solid)
+            ),
+            Self::MissingPixel { pos: (i, j), solid } => write!(
+                f,
+                "{} pixel ({i}, {j}) is covered by an existing touch but does not exist.",
+                side(solid)
+            ),
+            Self::ValidTouchOverlaps { pos: (i, j), solid } => write!(
+                f,
+                "valid {} touch ({i}, {j}) overlaps existing pixels of the other side.",
+                side(solid)
+            ),
+            Self::CoverageMismatch {
+                pos: (i, j),
+                solid,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "{} touch coverage at ({i}, {j}) is {actual}, expected {expected}.",
+                side(solid)
+            ),
+        }
+    }
+}
+
+impl Design {
+    /// Validate all cross-layer relationships of the design.
+    ///
+    /// Returns every violation found (empty if the design is consistent).
+    pub fn check_invariants(&self) -> Vec<Violation> {
+        let profiler = Profiler::start("check_invariants");
+        let (m, n) = self.shape;
+        let mut violations = Vec::new();
+
+        for k in 0..m * n {
+            if self[Layer::Void][k] & self[Layer::Solid][k] {
+                violations.push(Violation::Overlap {
+                    pos: (k / n, k % n),
+                });
+            }
+            if !self.active[k] & (self[Layer::Void][k] | self[Layer::Solid][k]) {
+                violations.push(Violation::InactiveAssigned {
+                    pos: (k / n, k % n),
+                });
+            }
+        }
+
+        for solid in [false, true] {
+            let side = |layer: Layer| if solid { layer.opposite() } else { layer };
+            let existing = &self[side(Layer::VoidPixelExisting)];
+            let impossible = &self[side(Layer::VoidPixelImpossible)];
+            let required = &self[side(Layer::VoidPixelRequired)];
+            let opposite_existing = &self[side(Layer::SolidPixelExisting)];
+            let opposite_impossible = &self[side(Layer::SolidPixelImpossible)];
+            let touch_existing = &self[side(Layer::VoidTouchExisting)];
+            let touch_invalid = &self[side(Layer::VoidTouchInvalid)];
+            let touch_required = &self[side(Layer::VoidTouchRequired)];
+            let (coverage, stencils) = if solid {
+                (&self.solid_touch_coverage, &self.solid_stencils)
+            } else {
+                (&self.void_touch_coverage, &self.stencils)
+            };
+
+            let mut stamped = vec![false; m * n];
+            for k in (0..m * n).filter(|k| touch_existing[*k]) {
+                stencils[self.brush_index[k]]
+                    .brush
+                    .apply(&mut stamped, (k / n, k % n), true);
+            }
+
+            for k in 0..m * n {
+                let pos = (k / n, k % n);
+                // inactive pixels exist without being assigned or stamped
+                let active = self.active[k];
+                if active & (self[side(Layer::Void)][k] != existing[k]) {
+                    violations.push(Violation::DesignMismatch { pos, solid });
+                }
+                if (impossible[k] as u8 + existing[k] as u8 + required[k] as u8) > 1 {
+                    violations.push(Violation::AmbiguousPixel { pos, solid });
+                }
+                if existing[k] & !opposite_impossible[k] {
+                    violations.push(Violation::ExistingNotExclusive { pos, solid });
+                }
+                if (touch_required[k] as u8 + touch_invalid[k] as u8 + touch_existing[k] as u8) > 1
+                {
+                    violations.push(Violation::AmbiguousTouch { pos, solid });
+                }
+                if active & existing[k] & !stamped[k] {
+                    violations.push(Violation::UnstampedPixel { pos, solid });
+                }
+                if stamped[k] & !existing[k] {
+                    violations.push(Violation::MissingPixel { pos, solid });
+                }
+                if !touch_invalid[k]
+                    & !touch_existing[k]
+                    & !stencils[self.brush_index[k]]
+                        .brush
+                        .all(pos, |p| !opposite_existing[p])
+                {
+                    violations.push(Violation::ValidTouchOverlaps { pos, solid });
+                }
+                let mut expected = 0;
+                for_each_touch(stencils, &self.brush_index, pos, |t| {
+                    if !touch_invalid[t] {
+                        expected += 1;
+                    }
+                });
+                if coverage[k] != expected {
+                    violations.push(Violation::CoverageMismatch {
+                        pos,
+                        solid,
+                        expected,
+                        actual: coverage[k],
+                    });
+                }
+            }
+        }
+        profiler.stop();
+        violations
+    }
+}
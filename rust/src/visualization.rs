@@ -1,6 +1,7 @@
 use super::brushes::Brush;
 use super::debug::Profiler;
 use super::design::Design;
+use super::layers::Layer;
 use super::status::Status;
 use itertools::izip;
 
@@ -206,10 +207,9 @@ pub fn test_visualization() {
 impl Design {
     pub fn design_view(&self) -> Vec<Status> {
         let profiler = Profiler::start("design_view");
-        let result = self
-            .void
+        let result = self[Layer::Void]
             .iter()
-            .zip(self.solid.iter())
+            .zip(self[Layer::Solid].iter())
             .map(|(v, s)| {
                 if *v {
                     Status::Void
@@ -227,9 +227,9 @@ impl Design {
     pub fn void_pixel_view(&self) -> Vec<Status> {
         let profiler = Profiler::start("void_pixel_view");
         let result = izip!(
-            self.void_pixel_impossible.iter(),
-            self.void_pixel_existing.iter(),
-            self.void_pixel_required.iter(),
+            self[Layer::VoidPixelImpossible].iter(),
+            self[Layer::VoidPixelExisting].iter(),
+            self[Layer::VoidPixelRequired].iter(),
         )
         .map(|(i, e, r)| {
             if *r {
@@ -250,9 +250,9 @@ impl Design {
     pub fn solid_pixel_view(&self) -> Vec<Status> {
         let profiler = Profiler::start("solid_pixel_view");
         let result = izip!(
-            self.solid_pixel_impossible.iter(),
-            self.solid_pixel_existing.iter(),
-            self.solid_pixel_required.iter(),
+            self[Layer::SolidPixelImpossible].iter(),
+            self[Layer::SolidPixelExisting].iter(),
+            self[Layer::SolidPixelRequired].iter(),
         )
         .map(|(i, e, r)| {
             if *r {
@@ -273,9 +273,9 @@ impl Design {
     pub fn void_touches_view(&self) -> Vec<Status> {
         let profiler = Profiler::start("void_touches_view");
         let result = izip!(
-            self.void_touch_required.iter(),
-            self.void_touch_invalid.iter(),
-            self.void_touch_existing.iter(),
+            self[Layer::VoidTouchRequired].iter(),
+            self[Layer::VoidTouchInvalid].iter(),
+            self[Layer::VoidTouchExisting].iter(),
         )
         .map(|(r, i, e)| {
             if *e {
@@ -296,9 +296,9 @@ impl Design {
     pub fn solid_touches_view(&self) -> Vec<Status> {
         let profiler = Profiler::start("solid_touches_view");
         let result = izip!(
-            self.solid_touch_required.iter(),
-            self.solid_touch_invalid.iter(),
-            self.solid_touch_existing.iter(),
+            self[Layer::SolidTouchRequired].iter(),
+            self[Layer::SolidTouchInvalid].iter(),
+            self[Layer::SolidTouchExisting].iter(),
         )
         .map(|(r, i, e)| {
             if *e {
@@ -0,0 +1,274 @@
+use super::array::new_array;
+use super::brushes::{Brush, BrushError, BrushIssue};
+use super::design::{Design, InactivePolicy};
+use super::generator::{
+    complete_feasible_design, GeneratorError, GeneratorOptions, GeneratorReport,
+};
+use super::grid::{minkowski_sum, Grid};
+use super::layers::Layer;
+use super::stencil::{Stencil, Stencils};
+
+pub fn test_voxels() {
+    let (l, m, n) = (6, 24, 24);
+    let brush = VoxelBrush::sphere(5);
+    brush.visualize();
+
+    // a smooth latent with features along all three axes
+    let latent_t: Vec<f32> = (0..l * m * n)
+        .map(|k| {
+            let (a, i, j) = ((k / (m * n)) as f32, ((k / n) % m) as f32, (k % n) as f32);
+            (i / 3.0).sin() + (j / 4.0).cos() + (a / 2.0).sin() - 0.3
+        })
+        .collect();
+    let (design, _) =
+        generate_feasible_voxel_design((l, m, n), &latent_t, brush, &GeneratorOptions::default())
+            .unwrap();
+    design.visualize();
+    println!(
+        "feasible: {}, invariant violations: {}, solid voxels: {}",
+        design.is_feasible(),
+        design.check_invariants().len(),
+        design[Layer::Solid].iter().filter(|s| **s).count()
+    );
+
+    // empty brushes and brushes without a center voxel are rejected
+    let empty = VoxelBrush::from_f32_mask((1, 1, 1), &[0.0]);
+    for brush in [empty, VoxelBrush::sphere(4)] {
+        if let Err(error) = Design::voxel((l, m, n), brush) {
+            println!("{error}");
+        }
+    }
+}
+
+/// A brush on a voxel grid, with offsets `(depth, row, column)`.
+#[derive(Clone)]
+pub struct VoxelBrush {
+    pub voxels: Vec<(i32, i32, i32)>,
+    pub shape: (usize, usize, usize),
+}
+
+impl VoxelBrush {
+    /// The voxels whose center lies within a ball of `diameter` voxels.
+    pub fn sphere(diameter: usize) -> Self {
+        let d = diameter;
+        let mask: Vec<f32> = (0..d * d * d)
+            .map(|k| {
+                let (a, i, j) = (k / (d * d), (k / d) % d, k % d);
+                in_ball(&[a, i, j], d) as u8 as f32
+            })
+            .collect();
+        Self::from_f32_mask((d, d, d), &mask)
+    }
+
+    /// A disk of `diameter` voxels extruded over `height` voxels along the depth axis.
+    pub fn cylinder(diameter: usize, height: usize) -> Self {
+        let d = diameter;
+        let mask: Vec<f32> = (0..height * d * d)
+            .map(|k| {
+                let (i, j) = ((k / d) % d, k % d);
+                in_ball(&[i, j], d) as u8 as f32
+            })
+            .collect();
+        Self::from_f32_mask((height, d, d), &mask)
+    }
+
+    pub fn from_f32_mask(shape: (usize, usize, usize), mask: &[f32]) -> Self {
+        let (l, m, n) = shape;
+        let (l_, m_, n_) = (l as i32 / 2, m as i32 / 2, n as i32 / 2);
+        let mut voxels = Vec::new();
+        for a in 0..l {
+            for i in 0..m {
+                for j in 0..n {
+                    if mask[(a * m + i) * n + j] > 0.5 {
+                        voxels.push((a as i32 - l_, i as i32 - m_, j as i32 - n_));
+                    }
+                }
+            }
+        }
+        Self { voxels, shape }
+    }
+
+    pub fn mask(&self) -> Vec<bool> {
+        let (l, m, n) = self.shape;
+        let mut mask = new_array(l * m * n, false);
+        for (a, i, j) in self.voxels.iter() {
+            let a = a + (l as i32) / 2;
+            let i = i + (m as i32) / 2;
+            let j = j + (n as i32) / 2;
+            if (a < 0) | (i < 0) | (j < 0) {
+                continue;
+            }
+            if (l as i32 <= a) | (m as i32 <= i) | (n as i32 <= j) {
+                continue;
+            }
+            mask[((a as usize) * m + i as usize) * n + j as usize] = true;
+        }
+        mask
+    }
+
+    /// The offsets as grid offsets (see `Stencil::from_grid_offsets`).
+    pub fn offsets(&self) -> Vec<Vec<i32>> {
+        self.voxels
+            .iter()
+            .map(|(a, i, j)| vec![*a, *i, *j])
+            .collect()
+    }
+
+    fn from_offsets(offsets: Vec<Vec<i32>>) -> Self {
+        let extent = |axis: usize| {
+            let max = offsets.iter().map(|o| o[axis].abs()).max().unwrap_or(0);
+            2 * max as usize + 1
+        };
+        let shape = (extent(0), extent(1), extent(2));
+        let voxels = offsets.iter().map(|o| (o[0], o[1], o[2])).collect();
+        Self { voxels, shape }
+    }
+
+    /// The projection of the brush onto the last two axes.
+    pub fn footprint(&self) -> Brush {
+        let (_, m, n) = self.shape;
+        let mut brush: Vec<(i32, i32)> = self.voxels.iter().map(|(_, i, j)| (*i, *j)).collect();
+        brush.sort();
+        brush.dedup();
+        Brush {
+            brush,
+            shape: (m, n),
+        }
+    }
+
+    /// The slice of the brush at depth offset `a`.
+    pub fn slice(&self, a: i32) -> Brush {
+        let (_, m, n) = self.shape;
+        let brush = self
+            .voxels
+            .iter()
+            .filter(|(a_, _, _)| *a_ == a)
+            .map(|(_, i, j)| (*i, *j))
+            .collect();
+        Brush {
+            brush,
+            shape: (m, n),
+        }
+    }
+
+    /// Reject a brush the generator cannot use: an empty brush, one without
+    /// its origin, or one whose shape is not odd along every axis (see
+    /// `Brush::check`).
+    pub fn check(&self) -> Result<(), BrushError> {
+        let (l, m, n) = self.shape;
+        let mut issues = Vec::new();
+        if self.voxels.is_empty() {
+            issues.push(BrushIssue::Empty);
+        } else if !self.voxels.contains(&(0, 0, 0)) {
+            issues.push(BrushIssue::MissingOrigin);
+        }
+        if (l % 2 == 0) | (m % 2 == 0) | (n % 2 == 0) {
+            issues.push(BrushIssue::EvenShape);
+        }
+        if !issues.is_empty() {
+            return Err(BrushError { issues });
+        }
+        Ok(())
+    }
+
+    pub fn visualize(&self) {
+        let (l, _, _) = self.shape;
+        for a in 0..l as i32 {
+            self.slice(a - l as i32 / 2).visualize();
+        }
+    }
+}
+
+/// Whether the center of voxel `pos` lies in the ball inscribed in a cube of `diameter` voxels.
+fn in_ball(pos: &[usize], diameter: usize) -> bool {
+    let center = (diameter as f32 - 1.0) / 2.0;
+    let radius = diameter as f32 / 2.0;
+    let distance2: f32 = pos.iter().map(|p| (*p as f32 - center).powi(2)).sum();
+    distance2 <= radius * radius
+}
+
+/// The offsets of all touches whose brush overlaps the brush at the origin.
+pub fn compute_big_voxel_brush(brush: &VoxelBrush) -> VoxelBrush {
+    let reflected: Vec<Vec<i32>> = brush
+        .offsets()
+        .iter()
+        .map(|o| o.iter().map(|x| -x).collect())
+        .collect();
+    VoxelBrush::from_offsets(minkowski_sum(&brush.offsets(), &reflected))
+}
+
+/// The touches that can become free after a touch at the origin (see `compute_very_big_brush`).
+pub fn compute_very_big_voxel_brush(brush: &VoxelBrush) -> VoxelBrush {
+    let big_brush = compute_big_voxel_brush(brush).offsets();
+    VoxelBrush::from_offsets(minkowski_sum(&big_brush, &big_brush))
+}
+
+impl Design {
+    /// A design on a voxel grid of `shape` `(depth, rows, columns)`.
+    ///
+    /// The design is addressed through the planar view of the grid (see
+    /// `Grid`): voxel `(a, i, j)` is position `(a * rows + i, j)`, so the
+    /// layers, the generator and the invariant checks work unchanged, and
+    /// `visualize` shows the depth slices one below the other. The `brush`
+    /// fields hold the footprints of the voxel brushes. Returns an error for
+    /// brushes the generator cannot use (see `VoxelBrush::check`).
+    pub fn voxel(shape: (usize, usize, usize), brush: VoxelBrush) -> Result<Self, GeneratorError> {
+        let (l, m, n) = shape;
+        let active = vec![true; l * m * n];
+        Self::voxel_with_active_region(shape, brush, active, InactivePolicy::Forbidden)
+    }
+
+    /// A voxel design restricted to the voxels marked in `active` (see `Design::with_active_region`).
+    pub fn voxel_with_active_region(
+        shape: (usize, usize, usize),
+        brush: VoxelBrush,
+        active: Vec<bool>,
+        policy: InactivePolicy,
+    ) -> Result<Self, GeneratorError> {
+        let grid = Grid::volume(shape);
+        if active.len() != grid.size() {
+            return Err(GeneratorError::ActiveRegionMismatch {
+                expected: grid.size(),
+                actual: active.len(),
+            });
+        }
+        brush.check().map_err(GeneratorError::InvalidBrush)?;
+        let big_brush = compute_big_voxel_brush(&brush);
+        let very_big_brush = compute_very_big_voxel_brush(&brush);
+        let reflected: Vec<Vec<i32>> = brush
+            .offsets()
+            .iter()
+            .map(|o| o.iter().map(|x| -x).collect())
+            .collect();
+        let stencils = Stencils {
+            brush: Stencil::from_grid_offsets(&brush.offsets(), grid.clone()),
+            reflected_brush: Stencil::from_grid_offsets(&reflected, grid.clone()),
+            big_brushes: vec![Stencil::from_grid_offsets(
+                &big_brush.offsets(),
+                grid.clone(),
+            )],
+            very_big_brush: Stencil::from_grid_offsets(&very_big_brush.offsets(), grid.clone()),
+        };
+        let footprint = brush.footprint();
+        Ok(Self::with_stencils(
+            (vec![footprint.clone()], vec![footprint]),
+            big_brush.footprint(),
+            very_big_brush.footprint(),
+            (vec![stencils.clone()], vec![stencils]),
+            vec![0; grid.size()],
+            active,
+            policy,
+        ))
+    }
+}
+
+/// Generate a feasible voxel design; `latent_t` is indexed like the grid.
+pub fn generate_feasible_voxel_design(
+    shape: (usize, usize, usize),
+    latent_t: &[f32],
+    brush: VoxelBrush,
+    options: &GeneratorOptions,
+) -> Result<(Design, GeneratorReport), GeneratorError> {
+    let design = Design::voxel(shape, brush)?;
+    complete_feasible_design(design, latent_t, options)
+}
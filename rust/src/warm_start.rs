@@ -0,0 +1,264 @@
+use super::array::{new_array, read_f32};
+use super::brushes::Brush;
+use super::debug::{now, since, Profiler};
+use super::design::{Design, InactivePolicy};
+use super::generator::{
+    check_latent, complete_feasible_design, generate_feasible_design_with_options,
+    resolve_required_void_pixels, GeneratorError, GeneratorOptions, GeneratorReport,
+    UnresolvedStrategy,
+};
+use super::layers::Layer;
+use super::stencil::Stencil;
+
+pub fn test_warm_start() {
+    let seed = 42;
+    let (m, n) = (300, 300);
+    let latent_t = read_f32(&format!("latent_t_{seed}_{m}x{n}.bin"));
+    // flip the sign of 0.1% of the latent, spread over the grid
+    let changed = |stride: usize| -> Vec<f32> {
+        latent_t
+            .iter()
+            .enumerate()
+            .map(|(k, l)| if k % stride == 0 { -l } else { *l })
+            .collect()
+    };
+    let options = GeneratorOptions::default();
+    let brush = Brush::notched_square(5, 1);
+    let (previous, _) =
+        generate_feasible_design_with_options((m, n), &latent_t, brush.clone(), &options).unwrap();
+
+    for stride in [997, 97] {
+        let latent_changed = changed(stride);
+        let start_time = now();
+        generate_feasible_design_with_options((m, n), &latent_changed, brush.clone(), &options)
+            .unwrap();
+        let cold_time = since(start_time);
+        let start_time = now();
+        let (design, report) =
+            generate_feasible_design_warm(&previous, &latent_t, &latent_changed, 0.0, &options)
+                .unwrap();
+        let warm_time = since(start_time);
+        println!(
+            "{} pixels changed: cold {cold_time:.3}s, warm {warm_time:.3}s ({:.1}x), reused touches: {}, feasible: {}, invariant violations: {}",
+            (m * n).div_ceil(stride),
+            cold_time / warm_time,
+            report.reused_touches,
+            design.is_feasible(),
+            design.check_invariants().len()
+        );
+    }
+
+    // the unresolved pixels of a previous design (see `test_backtrack`) are
+    // kept, so the warm start fails and the design is regenerated from scratch.
+    let brush = Brush::notched_square(3, 0);
+    let (previous, _) =
+        generate_feasible_design_with_options((m, n), &latent_t, brush, &options).unwrap();
+    let backtrack = GeneratorOptions {
+        unresolved_strategy: UnresolvedStrategy::Backtrack,
+        ..GeneratorOptions::default()
+    };
+    let (design, report) =
+        generate_feasible_design_warm(&previous, &latent_t, &changed(997), 0.0, &backtrack)
+            .unwrap();
+    println!(
+        "previous feasible: {}, regenerated from scratch: {}, feasible: {}",
+        previous.is_feasible(),
+        report.reused_touches == 0,
+        design.is_feasible()
+    );
+}
+
+/// Generate a feasible design for `latent_t`, reusing a design generated
+/// earlier for `previous_latent_t`.
+///
+/// The previous design is kept as it is, except around the pixels whose latent
+/// value changed by more than `tolerance` (or changed sign): the touches within
+/// a `very_big_brush` of them are removed (see `Design::remove_void_touches`)
+/// and only that part of the design is regenerated. Dead ends there are
+/// backtracked. If less than a quarter of the touches can be kept, or the
+/// warm-started design still turns out infeasible, the design is regenerated
+/// from scratch instead (and no touches are reused).
+pub fn generate_feasible_design_warm(
+    previous: &Design,
+    previous_latent_t: &[f32],
+    latent_t: &[f32],
+    tolerance: f32,
+    options: &GeneratorOptions,
+) -> Result<(Design, GeneratorReport), GeneratorError> {
+    let shape = previous.shape;
+    check_latent(shape, previous_latent_t)?;
+    check_latent(shape, latent_t)?;
+    let profiler = Profiler::start("generate_feasible_design_warm");
+    let (m, n) = shape;
+
+    let very_big_brush = Stencil::new(&previous.very_big_brush, shape);
+    let mut affected = new_array(m * n, false);
+    for k in 0..m * n {
+        let (old, new) = (previous_latent_t[k], latent_t[k]);
+        if ((new - old).abs() > tolerance) | ((new > 0.0) != (old > 0.0)) {
+            very_big_brush.apply(&mut affected, (k / n, k % n), true);
+        }
+    }
+
+    let removed_touches = |touches: &Vec<bool>| -> Vec<(usize, usize)> {
+        (0..m * n)
+            .filter(|k| touches[*k] & affected[*k])
+            .map(|k| (k / n, k % n))
+            .collect()
+    };
+    let void_touches = removed_touches(&previous[Layer::VoidTouchExisting]);
+    let solid_touches = removed_touches(&previous[Layer::SolidTouchExisting]);
+    let existing_touches = (0..m * n)
+        .filter(|k| {
+            previous[Layer::VoidTouchExisting][*k] | previous[Layer::SolidTouchExisting][*k]
+        })
+        .count();
+    let reused_touches = existing_touches - void_touches.len() - solid_touches.len();
+
+    let mut warm = None;
+    // regenerating most of the design costs more than starting over
+    if 4 * reused_touches >= existing_touches {
+        let mut design = previous.clone();
+        design.remove_void_touches(&void_touches);
+        design.remove_solid_touches(&solid_touches);
+        let warm_options = GeneratorOptions {
+            unresolved_strategy: UnresolvedStrategy::Backtrack,
+            ..options.clone()
+        };
+        match complete_seeded_design(design, &Vec::new(), &Vec::new(), latent_t, &warm_options) {
+            Ok((design, mut report)) if design.is_feasible() => {
+                report.reused_touches = reused_touches;
+                warm = Some((design, report));
+            }
+            Ok(_) | Err(GeneratorError::UnresolvedPixels { .. }) => {
+                if options.verbose {
+                    println!("warm start failed, regenerating from scratch.");
+                }
+            }
+            Err(error) => return Err(error),
+        }
+    }
+
+    let result = match warm {
+        Some(result) => result,
+        None => {
+            let design = Design::with_brush_map(
+                shape,
+                previous.brushes.clone(),
+                previous.solid_brushes.clone(),
+                previous.brush_index.clone(),
+                previous.active.clone(),
+                previous.inactive_policy,
+            );
+            complete_seeded_design(design, &Vec::new(), &Vec::new(), latent_t, options)?
+        }
+    };
+    profiler.stop();
+    Ok(result)
+}
+
+/// Seed an empty design with the given touches and complete it into a feasible design.
+///
+/// Seed touches are only stamped (no free touch search), so they should be
+/// mutually compatible; seeds that became invalid by an earlier seed are skipped.
+pub fn complete_from_touches(
+    shape: (usize, usize),
+    brush: Brush,
+    void_touches: &[(usize, usize)],
+    solid_touches: &[(usize, usize)],
+    latent_t: &[f32],
+    options: &GeneratorOptions,
+) -> Result<(Design, GeneratorReport), GeneratorError> {
+    let (m, n) = shape;
+    let active = vec![true; m * n];
+    let policy = InactivePolicy::Forbidden;
+    let (design, brush_issues) =
+        Design::try_with_brushes(shape, brush.clone(), brush, active, policy)?;
+    let (design, mut report) =
+        complete_seeded_design(design, void_touches, solid_touches, latent_t, options)?;
+    report.brush_issues = brush_issues;
+    Ok((design, report))
+}
+
+/// Like `complete_from_touches`, but starting from the given (empty) design,
+/// e.g. a design restricted to an active region.
+pub fn complete_seeded_design(
+    design: Design,
+    void_touches: &[(usize, usize)],
+    solid_touches: &[(usize, usize)],
+    latent_t: &[f32],
+    options: &GeneratorOptions,
+) -> Result<(Design, GeneratorReport), GeneratorError> {
+    check_latent(design.shape, latent_t)?;
+    let profiler = Profiler::start("complete_from_touches");
+    let mut design = design;
+    let reused_void = stamp_void_touches(&mut design, void_touches);
+    design.invert();
+    let reused_solid = stamp_void_touches(&mut design, solid_touches);
+    design.invert();
+
+    let void_latent_t: Vec<f32> = latent_t.iter().map(|l| -l).collect();
+    resolve_seeded_void_pixels(&mut design, &void_latent_t, false, options.verbose);
+    design.invert();
+    resolve_seeded_void_pixels(&mut design, latent_t, true, options.verbose);
+    design.invert();
+
+    design.take_all_free_void_touches();
+    design.invert();
+    design.take_all_free_void_touches();
+    design.invert();
+
+    let (design, mut report) = complete_feasible_design(design, latent_t, options)?;
+    report.reused_touches = reused_void + reused_solid;
+    profiler.stop();
+    Ok((design, report))
+}
+
+fn stamp_void_touches(design: &mut Design, touches: &[(usize, usize)]) -> usize {
+    let (_, n) = design.shape;
+    let mut num_stamped = 0;
+    for (i, j) in touches.iter() {
+        if design[Layer::VoidTouchInvalid][i * n + j] | design[Layer::VoidTouchExisting][i * n + j]
+        {
+            continue;
+        }
+        // touches inside the seeded pixels are taken as free touches later on
+        let void_pixel_existing = &design[Layer::VoidPixelExisting];
+        if design
+            .stencils_at((*i, *j))
+            .brush
+            .all((*i, *j), |k| void_pixel_existing[k])
+        {
+            num_stamped += 1;
+            continue;
+        }
+        design.stamp_void_touch((*i, *j));
+        num_stamped += 1;
+    }
+    num_stamped
+}
+
+fn resolve_seeded_void_pixels(
+    design: &mut Design,
+    void_latent_t: &[f32],
+    is_solid_touch: bool,
+    verbose: bool,
+) {
+    let (m, n) = design.shape;
+    let unassigned: Vec<(usize, usize)> = (0..m * n)
+        .filter(|k| {
+            !(design[Layer::VoidPixelExisting][*k] | design[Layer::VoidPixelImpossible][*k])
+        })
+        .map(|k| (k / n, k % n))
+        .collect();
+    let mut required_pixels = design.find_required_pixels(unassigned);
+    let mut resolving_touches = design.find_resolving_touches_for_required_pixels(&required_pixels);
+    resolve_required_void_pixels(
+        design,
+        &mut required_pixels,
+        &mut resolving_touches,
+        void_latent_t,
+        is_solid_touch,
+        verbose,
+    );
+}